@@ -0,0 +1,689 @@
+use crate::filter::IGNORE_FILE;
+use crate::{ProjectSize, ScanOptions, add_entry_size};
+use anyhow::{Context, Result};
+use ignore::gitignore::Gitignore;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// What earlier scans found inside each project, so the next scan can skip
+// re-reading folders that haven't changed. One file per project under
+// `$XDG_CACHE_HOME/sweeper/index`.
+//
+// Each folder has a record of its direct entries as of the folder's mtime:
+// their sizes, its subfolders, and the newest mtime among its files. A folder
+// whose mtime still matches is taken from its record instead of being listed
+// again; its subfolders are still checked, as changes below a folder don't
+// touch its mtime. Neither does a file rewritten in place, so the index can
+// miss fresh activity: a project it finds stale is walked again from disk
+// before it is reported (see `walk_cached`). Files only count towards size
+// there, and a size can lag until something else in the folder changes (or
+// with `--no-cache`).
+
+// Bump when `ProjectIndex` changes shape; older files are then ignored.
+const INDEX_VERSION: u32 = 2;
+
+// How old a temp file must be before `prune` takes it for a leftover rather
+// than the index another scan is writing right now.
+const STALE_TMP_AGE: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ProjectIndex {
+    version: u32,
+    path: PathBuf,
+    scanned_at: SystemTime,
+    // Hash of the project's `.sweeperignore`, which decides which files count
+    // towards `DirRecord::newest`. Those are only reused while it matches.
+    ignore_hash: Option<String>,
+    // What the scan that wrote this found.
+    newest: Option<SystemTime>,
+    size: Option<ProjectSize>,
+    // Keyed by path relative to the project, `/`-separated.
+    dirs: BTreeMap<String, DirRecord>,
+}
+
+// The direct entries of one folder, as of `mtime`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DirRecord {
+    mtime: SystemTime,
+    // Everything but folders and hardlinked files.
+    files: ProjectSize,
+    // Hardlinked files, kept apart so a file linked from two folders is still
+    // only counted once.
+    links: Vec<Link>,
+    subdirs: Vec<String>,
+    // The newest mtime among everything but folders, leaving out what the
+    // project's `.sweeperignore` matches.
+    newest: Option<SystemTime>,
+    // Subfolders whose names aren't UTF-8. A folder with any is never stored
+    // and so gets listed afresh every time.
+    #[serde(skip)]
+    odd_subdirs: Vec<OsString>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Link {
+    dev: u64,
+    ino: u64,
+    is_file: bool,
+    apparent_bytes: u64,
+    disk_bytes: u64,
+}
+
+// What a walk of one project found.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Walked {
+    // As `walk_tree` would find it.
+    pub newest: Option<SystemTime>,
+    // None when the walk stopped early or didn't size the project.
+    pub size: Option<ProjectSize>,
+    // Whether any folder's activity came from the index rather than from disk.
+    pub reused: bool,
+}
+
+pub fn index_dir() -> Result<PathBuf> {
+    let cache_dir = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(dirs::cache_dir)
+        .context("Cannot determine a cache directory for the scan index")?;
+    Ok(cache_dir.join("sweeper").join("index"))
+}
+
+fn index_path(index_dir: &Path, project: &Path) -> PathBuf {
+    let hash = blake3::hash(project.as_os_str().as_encoded_bytes());
+    index_dir.join(format!("{}.json", &hash.to_hex()[..32]))
+}
+
+fn load(index_dir: &Path, project: &Path) -> Option<ProjectIndex> {
+    let text = fs::read_to_string(index_path(index_dir, project)).ok()?;
+    serde_json::from_str::<ProjectIndex>(&text)
+        .ok()
+        .filter(|i| i.version == INDEX_VERSION && i.path == project)
+}
+
+fn save(index_dir: &Path, index: &ProjectIndex) -> Result<()> {
+    let path = index_path(index_dir, &index.path);
+    fs::create_dir_all(index_dir)
+        .with_context(|| format!("Failed to create dir: {}", index_dir.display()))?;
+    // Per process, so two scans of the same root don't write the same temp file.
+    let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+    fs::write(&tmp, serde_json::to_string(index)?)
+        .with_context(|| format!("Failed to write: {}", tmp.display()))?;
+    fs::rename(&tmp, &path).with_context(|| format!("Failed to write: {}", path.display()))
+}
+
+// The newest mtime in a project, taking every folder whose mtime is unchanged
+// from the index. Quick, but blind to files rewritten in place; see
+// `walk_cached`. The index is best effort: failing to read or write it never
+// fails the scan.
+pub(crate) fn newest_cached(
+    dir: &Path,
+    opts: &ScanOptions,
+    stop_after: Option<SystemTime>,
+    ignore: Option<&Gitignore>,
+) -> Walked {
+    match index_dir() {
+        Ok(index_dir) => walk_in(&index_dir, dir, opts, stop_after, ignore, false),
+        Err(_) => Walked {
+            newest: crate::walk_tree(dir, opts, stop_after, ignore),
+            ..Walked::default()
+        },
+    }
+}
+
+// The newest mtime as read from disk, and the project's size with unchanged
+// folders outside the activity walk taken from the index.
+pub(crate) fn walk_cached(
+    dir: &Path,
+    opts: &ScanOptions,
+    stop_after: Option<SystemTime>,
+    ignore: Option<&Gitignore>,
+) -> Walked {
+    match index_dir() {
+        Ok(index_dir) => walk_in(&index_dir, dir, opts, stop_after, ignore, true),
+        Err(_) => Walked {
+            newest: crate::walk_tree(dir, opts, stop_after, ignore),
+            size: opts.sizes.then(|| crate::dir_size(dir)),
+            reused: false,
+        },
+    }
+}
+
+fn walk_in(
+    index_dir: &Path,
+    dir: &Path,
+    opts: &ScanOptions,
+    stop_after: Option<SystemTime>,
+    ignore: Option<&Gitignore>,
+    live: bool,
+) -> Walked {
+    let ignore_hash = fs::read(dir.join(IGNORE_FILE))
+        .ok()
+        .map(|bytes| blake3::hash(&bytes).to_hex().to_string());
+    let old = load(index_dir, dir);
+    let mut walk = Walk {
+        opts,
+        ignore,
+        max_depth: match opts.depth {
+            crate::Depth::Limited(n) => n,
+            crate::Depth::Unlimited => usize::MAX,
+        },
+        stop_after,
+        live,
+        sizes: live && opts.sizes,
+        old: old.as_ref().map(|i| &i.dirs),
+        old_activity: old.as_ref().is_some_and(|i| i.ignore_hash == ignore_hash),
+        dirs: BTreeMap::new(),
+        newest: None,
+        size: ProjectSize::default(),
+        seen: HashSet::new(),
+        stopped: false,
+        reused: false,
+    };
+
+    // Like walkdir, follow the project folder itself if it is a symlink.
+    if let Ok(meta) = fs::metadata(dir) {
+        add_entry_size(&mut walk.size, &meta, &mut walk.seen);
+        if let Ok(mtime) = meta.modified() {
+            walk.consider(mtime);
+        }
+        if !walk.stopped {
+            walk.visit(dir, Some(String::new()), 0, false);
+        }
+    }
+
+    let walked = Walked {
+        newest: walk.newest,
+        size: (walk.sizes && !walk.stopped).then_some(walk.size),
+        reused: walk.reused,
+    };
+
+    // Folders this walk didn't get to (early exit, or outside the activity walk
+    // when not sizing) keep what the index knew, unless they are gone.
+    let mut dirs = walk.dirs;
+    if let Some(old) = old {
+        let mut gone: Vec<String> = Vec::new();
+        for (rel, record) in old.dirs {
+            if dirs.contains_key(&rel) {
+                continue;
+            }
+            let (parent, name) = rel.rsplit_once('/').unwrap_or(("", &rel));
+            let dropped = gone.iter().any(|g| rel.starts_with(&format!("{}/", g)))
+                || dirs
+                    .get(parent)
+                    .is_some_and(|p| !p.subdirs.iter().any(|s| s == name));
+            if dropped {
+                gone.push(rel);
+            } else {
+                dirs.insert(rel, record);
+            }
+        }
+    }
+    let _ = save(
+        index_dir,
+        &ProjectIndex {
+            version: INDEX_VERSION,
+            path: dir.to_path_buf(),
+            scanned_at: SystemTime::now(),
+            ignore_hash,
+            newest: walked.newest,
+            size: walked.size,
+            dirs,
+        },
+    );
+
+    walked
+}
+
+struct Walk<'a> {
+    opts: &'a ScanOptions,
+    // The project's `.sweeperignore`.
+    ignore: Option<&'a Gitignore>,
+    max_depth: usize,
+    stop_after: Option<SystemTime>,
+    // List every folder in the activity walk from disk.
+    live: bool,
+    // Total up sizes, which takes visiting the folders outside the activity walk.
+    sizes: bool,
+    old: Option<&'a BTreeMap<String, DirRecord>>,
+    // Whether the old records' `newest` can be trusted.
+    old_activity: bool,
+    dirs: BTreeMap<String, DirRecord>,
+    newest: Option<SystemTime>,
+    size: ProjectSize,
+    seen: HashSet<(u64, u64)>,
+    stopped: bool,
+    reused: bool,
+}
+
+impl Walk<'_> {
+    fn consider(&mut self, mtime: SystemTime) {
+        self.newest = Some(self.newest.map_or(mtime, |cur| cur.max(mtime)));
+        if self.stop_after.is_some_and(|cutoff| mtime > cutoff) {
+            self.stopped = true;
+        }
+    }
+
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let by_name = || {
+            let name = path.file_name().and_then(|n| n.to_str());
+            name.is_some_and(|name| self.opts.ignore_dirs.iter().any(|n| n == name))
+        };
+        path.file_name() == Some(OsStr::new(".git"))
+            || (is_dir && by_name())
+            || self
+                .ignore
+                .is_some_and(|i| i.matched(path, is_dir).is_ignore())
+    }
+
+    // The record for the folder `path` as of `mtime`: from the index when it
+    // is unchanged there and `reuse` allows, otherwise listed afresh.
+    fn record(
+        &self,
+        path: &Path,
+        rel: Option<&str>,
+        mtime: SystemTime,
+        reuse: bool,
+    ) -> Option<(DirRecord, bool)> {
+        let cached = rel
+            .filter(|_| reuse)
+            .and_then(|r| self.old?.get(r))
+            .filter(|r| r.mtime == mtime)
+            .cloned();
+        match cached {
+            Some(record) => Some((record, true)),
+            None => read_dir_record(path, mtime, self.ignore).map(|r| (r, false)),
+        }
+    }
+
+    fn add_record_size(&mut self, record: &DirRecord) {
+        self.size.add(&record.files);
+        for link in &record.links {
+            if self.seen.insert((link.dev, link.ino)) {
+                self.size.file_count += u64::from(link.is_file);
+                self.size.apparent_bytes += link.apparent_bytes;
+                self.size.disk_bytes += link.disk_bytes;
+            }
+        }
+    }
+
+    // The entries below `path`, a folder at `depth`. `rel` is None when the
+    // path can't be a key in the index (not UTF-8).
+    fn visit(&mut self, path: &Path, rel: Option<String>, depth: usize, ignored: bool) {
+        if ignored || depth >= self.max_depth {
+            if self.sizes {
+                self.visit_cached(path, rel);
+            }
+            return;
+        }
+
+        let Ok(mtime) = fs::symlink_metadata(path).and_then(|m| m.modified()) else {
+            return;
+        };
+        let reuse = !self.live && self.old_activity;
+        let Some((record, reused)) = self.record(path, rel.as_deref(), mtime, reuse) else {
+            return;
+        };
+        self.reused |= reused;
+        if self.sizes {
+            self.add_record_size(&record);
+        }
+        if let Some(newest) = record.newest {
+            self.consider(newest);
+        }
+
+        let subdirs = record.subdirs.iter().map(OsStr::new);
+        for name in subdirs.chain(record.odd_subdirs.iter().map(OsString::as_os_str)) {
+            if self.stopped {
+                break;
+            }
+            let child = path.join(name);
+            let Ok(meta) = fs::symlink_metadata(&child) else {
+                continue;
+            };
+            if !meta.is_dir() {
+                continue;
+            }
+            if self.sizes {
+                add_entry_size(&mut self.size, &meta, &mut self.seen);
+            }
+            let child_ignored = self.is_ignored(&child, true);
+            if !child_ignored && let Ok(mtime) = meta.modified() {
+                self.consider(mtime);
+            }
+            if !self.stopped {
+                let child_rel = join_rel(rel.as_deref(), name.to_str());
+                self.visit(&child, child_rel, depth + 1, child_ignored);
+            }
+        }
+
+        if let Some(rel) = rel
+            && record.odd_subdirs.is_empty()
+        {
+            self.dirs.insert(rel, record);
+        }
+    }
+
+    // Outside the activity walk only sizes matter, and a folder whose mtime
+    // matches the index is taken from there instead of being listed again.
+    fn visit_cached(&mut self, path: &Path, rel: Option<String>) {
+        let Ok(mtime) = fs::symlink_metadata(path).and_then(|m| m.modified()) else {
+            return;
+        };
+        let Some((record, _)) = self.record(path, rel.as_deref(), mtime, true) else {
+            return;
+        };
+
+        self.add_record_size(&record);
+        let subdirs = record.subdirs.iter().map(OsStr::new);
+        for name in subdirs.chain(record.odd_subdirs.iter().map(OsString::as_os_str)) {
+            let child = path.join(name);
+            let Ok(meta) = fs::symlink_metadata(&child) else {
+                continue;
+            };
+            if !meta.is_dir() {
+                continue;
+            }
+            add_entry_size(&mut self.size, &meta, &mut self.seen);
+            self.visit_cached(&child, join_rel(rel.as_deref(), name.to_str()));
+        }
+
+        if let Some(rel) = rel
+            && record.odd_subdirs.is_empty()
+        {
+            self.dirs.insert(rel, record);
+        }
+    }
+}
+
+fn join_rel(parent: Option<&str>, name: Option<&str>) -> Option<String> {
+    match (parent?, name?) {
+        ("", name) => Some(name.to_string()),
+        (parent, name) => Some(format!("{}/{}", parent, name)),
+    }
+}
+
+fn read_dir_record(
+    path: &Path,
+    mtime: SystemTime,
+    ignore: Option<&Gitignore>,
+) -> Option<DirRecord> {
+    let mut record = DirRecord {
+        mtime,
+        files: ProjectSize::default(),
+        links: Vec::new(),
+        subdirs: Vec::new(),
+        newest: None,
+        odd_subdirs: Vec::new(),
+    };
+    for entry in fs::read_dir(path).ok()?.flatten() {
+        let Ok(meta) = entry.metadata() else { continue };
+        if meta.is_dir() {
+            match entry.file_name().into_string() {
+                Ok(name) => record.subdirs.push(name),
+                Err(name) => record.odd_subdirs.push(name),
+            }
+            continue;
+        }
+        if let Some(link) = hardlink(&meta) {
+            record.links.push(link);
+        } else {
+            add_entry_size(&mut record.files, &meta, &mut HashSet::new());
+        }
+        let ignored = entry.file_name() == ".git"
+            || ignore.is_some_and(|i| i.matched(entry.path(), false).is_ignore());
+        if !ignored && let Ok(t) = meta.modified() {
+            record.newest = Some(record.newest.map_or(t, |cur| cur.max(t)));
+        }
+    }
+    Some(record)
+}
+
+#[cfg(unix)]
+fn hardlink(meta: &fs::Metadata) -> Option<Link> {
+    use std::os::unix::fs::MetadataExt;
+
+    (meta.nlink() > 1).then(|| Link {
+        dev: meta.dev(),
+        ino: meta.ino(),
+        is_file: meta.is_file(),
+        apparent_bytes: meta.len(),
+        disk_bytes: meta.blocks() * 512,
+    })
+}
+
+#[cfg(not(unix))]
+fn hardlink(_meta: &fs::Metadata) -> Option<Link> {
+    None
+}
+
+// An index file `prune` removed, and why.
+#[derive(Debug, Clone)]
+pub struct Pruned {
+    pub file: PathBuf,
+    pub project: Option<PathBuf>,
+    pub reason: &'static str,
+    pub bytes: u64,
+}
+
+// Drop index files for projects that are gone (or, with `all`, every one of
+// them), along with unreadable or outdated ones and temp files left behind by
+// scans that died. Anything else in the folder is left alone.
+pub fn prune(all: bool) -> Result<Vec<Pruned>> {
+    prune_in(&index_dir()?, all, SystemTime::now())
+}
+
+fn prune_in(dir: &Path, all: bool, now: SystemTime) -> Result<Vec<Pruned>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("read_dir failed: {}", dir.display())),
+    };
+
+    let mut pruned = Vec::new();
+    for entry in entries {
+        let file = entry?.path();
+        let Ok(meta) = fs::symlink_metadata(&file) else {
+            continue;
+        };
+        let bytes = meta.len();
+
+        match file.extension().and_then(OsStr::to_str) {
+            Some("json") => {}
+            Some("tmp") => {
+                let age = meta
+                    .modified()
+                    .ok()
+                    .and_then(|t| now.duration_since(t).ok());
+                if age.is_some_and(|age| age >= STALE_TMP_AGE) {
+                    fs::remove_file(&file)
+                        .with_context(|| format!("Failed to remove: {}", file.display()))?;
+                    pruned.push(Pruned {
+                        file,
+                        project: None,
+                        reason: "leftover",
+                        bytes,
+                    });
+                }
+                continue;
+            }
+            _ => continue,
+        }
+
+        let index = fs::read_to_string(&file)
+            .ok()
+            .and_then(|t| serde_json::from_str::<ProjectIndex>(&t).ok());
+        let (project, reason) = match index {
+            Some(i) if i.version != INDEX_VERSION => (Some(i.path), "outdated"),
+            Some(i) if !i.path.is_dir() => (Some(i.path), "project gone"),
+            Some(i) if all => (Some(i.path), "cleared"),
+            Some(_) => continue,
+            None => (None, "unreadable"),
+        };
+        fs::remove_file(&file).with_context(|| format!("Failed to remove: {}", file.display()))?;
+        pruned.push(Pruned {
+            file,
+            project,
+            reason,
+            bytes,
+        });
+    }
+    pruned.sort_by(|a, b| a.project.cmp(&b.project));
+    Ok(pruned)
+}
+
+pub fn print_prune(pruned: &[Pruned]) {
+    if pruned.is_empty() {
+        println!("Nothing to prune in the scan cache.");
+        return;
+    }
+    for p in pruned {
+        let what = p.project.as_ref().unwrap_or(&p.file).display().to_string();
+        println!("  - {}  ({})", what, p.reason);
+    }
+    let bytes: u64 = pruned.iter().map(|p| p.bytes).sum();
+    println!(
+        "\nPruned {} cache entries, {} freed.",
+        pruned.len(),
+        crate::fmt_bytes(bytes)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use filetime::FileTime;
+
+    fn old() -> FileTime {
+        FileTime::from_unix_time(1_600_000_000, 0)
+    }
+
+    fn write_old(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+        filetime::set_file_mtime(path, old()).unwrap();
+    }
+
+    // A project whose every file and folder is years old.
+    fn project(root: &Path) -> PathBuf {
+        let project = root.join("app");
+        write_old(&project.join("src/main.rs"), "fn main() {}\n");
+        write_old(&project.join("target/debug/app"), "binary");
+        for dir in ["src", "target/debug", "target", ""] {
+            filetime::set_file_mtime(project.join(dir), old()).unwrap();
+        }
+        project
+    }
+
+    fn walk(index: &Path, project: &Path, live: bool) -> Walked {
+        walk_in(index, project, &ScanOptions::default(), None, None, live)
+    }
+
+    #[test]
+    fn unchanged_folders_come_from_the_index() {
+        let tmp = tempfile::tempdir().unwrap();
+        let index = tmp.path().join("index");
+        let project = project(tmp.path());
+
+        let first = walk(&index, &project, true);
+        assert!(!first.reused);
+        assert_eq!(first.newest, Some(old().into()));
+        assert_eq!(first.size.unwrap().file_count, 2);
+
+        let second = walk(&index, &project, false);
+        assert!(second.reused);
+        assert_eq!(second.newest, first.newest);
+    }
+
+    #[test]
+    fn a_changed_folder_mtime_is_read_again() {
+        let tmp = tempfile::tempdir().unwrap();
+        let index = tmp.path().join("index");
+        let project = project(tmp.path());
+        walk(&index, &project, true);
+
+        // A new file bumps its folder's mtime, which invalidates the record.
+        fs::write(project.join("src/lib.rs"), "").unwrap();
+        let walked = walk(&index, &project, false);
+        assert!(walked.newest > Some(old().into()));
+    }
+
+    #[test]
+    fn in_place_edits_are_only_seen_by_a_live_walk() {
+        let tmp = tempfile::tempdir().unwrap();
+        let index = tmp.path().join("index");
+        let project = project(tmp.path());
+        walk(&index, &project, true);
+
+        // Rewriting a file leaves its folder's mtime alone.
+        fs::write(project.join("src/main.rs"), "fn main() { edited() }\n").unwrap();
+        filetime::set_file_mtime(project.join("src"), old()).unwrap();
+
+        let cached = walk(&index, &project, false);
+        assert!(cached.reused);
+        assert_eq!(cached.newest, Some(old().into()));
+        let live = walk(&index, &project, true);
+        assert!(!live.reused);
+        assert!(live.newest > Some(old().into()));
+    }
+
+    #[test]
+    fn a_changed_sweeperignore_drops_cached_activity() {
+        let tmp = tempfile::tempdir().unwrap();
+        let index = tmp.path().join("index");
+        let project = project(tmp.path());
+        walk(&index, &project, true);
+
+        fs::write(project.join(IGNORE_FILE), "*.log\n").unwrap();
+        filetime::set_file_mtime(project.join(IGNORE_FILE), old()).unwrap();
+        filetime::set_file_mtime(&project, old()).unwrap();
+        let walked = walk(&index, &project, false);
+        assert!(!walked.reused);
+    }
+
+    #[test]
+    fn records_of_removed_folders_are_dropped() {
+        let tmp = tempfile::tempdir().unwrap();
+        let index = tmp.path().join("index");
+        let project = project(tmp.path());
+        walk(&index, &project, true);
+
+        fs::remove_dir_all(project.join("target")).unwrap();
+        walk(&index, &project, false);
+        let saved = load(&index, &project).unwrap();
+        assert!(saved.dirs.contains_key("src"));
+        assert!(!saved.dirs.keys().any(|k| k.starts_with("target")));
+    }
+
+    #[test]
+    fn prune_leaves_fresh_temp_files_and_unknown_files_alone() {
+        let tmp = tempfile::tempdir().unwrap();
+        let index = tmp.path().join("index");
+        let project = project(tmp.path());
+        walk(&index, &project, true);
+
+        let in_flight = index.join("abc.1234.tmp");
+        let abandoned = index.join("def.5678.tmp");
+        let unknown = index.join("README");
+        for file in [&in_flight, &abandoned, &unknown] {
+            fs::write(file, "").unwrap();
+        }
+        filetime::set_file_mtime(&abandoned, old()).unwrap();
+
+        let pruned = prune_in(&index, false, SystemTime::now()).unwrap();
+        assert_eq!(pruned.len(), 1);
+        assert_eq!(pruned[0].file, abandoned);
+        assert!(in_flight.exists());
+        assert!(unknown.exists());
+        assert!(load(&index, &project).is_some());
+
+        fs::remove_dir_all(&project).unwrap();
+        let pruned = prune_in(&index, false, SystemTime::now()).unwrap();
+        assert_eq!(pruned.len(), 1);
+        assert_eq!(pruned[0].reason, "project gone");
+    }
+}
@@ -0,0 +1,547 @@
+use crate::compress::{self, Compression};
+use crate::journal;
+use crate::manifest::{self, Manifest};
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+// Per-file checksums of archived projects, taken from the source before it is
+// archived and kept next to the bucket manifest, so the archive can later be
+// re-hashed to catch files that went missing or rotted on disk.
+
+// `BUCKET/.sweeper-checksums/NAME.json`, one per archived project.
+pub const CHECKSUM_DIR: &str = ".sweeper-checksums";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Algorithm {
+    Blake3,
+    Sha256,
+}
+
+impl Algorithm {
+    pub fn label(self) -> &'static str {
+        match self {
+            Algorithm::Blake3 => "blake3",
+            Algorithm::Sha256 => "sha256",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileSum {
+    pub size: u64,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreeSums {
+    pub algorithm: Algorithm,
+    pub created_at: DateTime<Local>,
+    // Regular files only, by path relative to the project folder ('/'-separated).
+    pub files: BTreeMap<String, FileSum>,
+}
+
+// How a tree differs from its recorded checksums.
+#[derive(Debug, Clone, Default)]
+pub struct Diff {
+    pub missing: Vec<String>,
+    pub modified: Vec<String>,
+    pub extra: Vec<String>,
+}
+
+impl Diff {
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.modified.is_empty() && self.extra.is_empty()
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{} missing, {} modified, {} extra",
+            self.missing.len(),
+            self.modified.len(),
+            self.extra.len()
+        )
+    }
+}
+
+enum Hasher {
+    Blake3(Box<blake3::Hasher>),
+    Sha256(sha2::Sha256),
+}
+
+impl Hasher {
+    fn new(algorithm: Algorithm) -> Self {
+        match algorithm {
+            Algorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+            Algorithm::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Blake3(h) => {
+                h.update(data);
+            }
+            Hasher::Sha256(h) => h.update(data),
+        }
+    }
+
+    fn finish(self) -> String {
+        match self {
+            Hasher::Blake3(h) => h.finalize().to_hex().to_string(),
+            Hasher::Sha256(h) => h.finalize().iter().map(|b| format!("{:02x}", b)).collect(),
+        }
+    }
+}
+
+fn hash_reader(r: &mut impl Read, algorithm: Algorithm) -> io::Result<FileSum> {
+    let mut hasher = Hasher::new(algorithm);
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut size = 0u64;
+    loop {
+        match r.read(&mut buf)? {
+            0 => break,
+            n => {
+                hasher.update(&buf[..n]);
+                size += n as u64;
+            }
+        }
+    }
+    Ok(FileSum {
+        size,
+        hash: hasher.finish(),
+    })
+}
+
+fn key(rel: &Path) -> String {
+    rel.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// Checksums of every regular file under the folder `dir`.
+pub fn hash_tree(dir: &Path, algorithm: Algorithm) -> Result<TreeSums> {
+    let mut files = BTreeMap::new();
+    for entry in WalkDir::new(dir).follow_links(false) {
+        let entry = entry.with_context(|| format!("Failed to read: {}", dir.display()))?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let path = entry.path();
+        let mut file =
+            File::open(path).with_context(|| format!("Failed to open: {}", path.display()))?;
+        let sum = hash_reader(&mut file, algorithm)
+            .with_context(|| format!("Failed to read: {}", path.display()))?;
+        files.insert(key(path.strip_prefix(dir).unwrap_or(path)), sum);
+    }
+    Ok(TreeSums {
+        algorithm,
+        created_at: Local::now(),
+        files,
+    })
+}
+
+// Checksums of every regular file inside a `name.tar.*` archive, keyed as if
+// it were unpacked.
+fn hash_tarball(path: &Path, compression: Compression, algorithm: Algorithm) -> Result<TreeSums> {
+    let mut tar = tar::Archive::new(compression.reader(path)?);
+    let mut files = BTreeMap::new();
+    for entry in tar
+        .entries()
+        .with_context(|| format!("Failed to read: {}", path.display()))?
+    {
+        let mut entry = entry.with_context(|| format!("Failed to read: {}", path.display()))?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let rel = compress::strip_top(&entry.path()?)?;
+        let sum = hash_reader(&mut entry, algorithm)
+            .with_context(|| format!("Failed to read: {}", path.display()))?;
+        files.insert(key(&rel), sum);
+    }
+    Ok(TreeSums {
+        algorithm,
+        created_at: Local::now(),
+        files,
+    })
+}
+
+// Checksums of an archived project, whether kept as a folder or packed.
+pub fn hash_archived(path: &Path, algorithm: Algorithm) -> Result<TreeSums> {
+    match Compression::from_path(path) {
+        Some(compression) if path.is_file() => hash_tarball(path, compression, algorithm),
+        _ => hash_tree(path, algorithm),
+    }
+}
+
+pub fn compare(expected: &TreeSums, actual: &TreeSums) -> Diff {
+    let mut diff = Diff::default();
+    for (name, sum) in &expected.files {
+        match actual.files.get(name) {
+            None => diff.missing.push(name.clone()),
+            Some(found) if found != sum => diff.modified.push(name.clone()),
+            Some(_) => {}
+        }
+    }
+    diff.extra = actual
+        .files
+        .keys()
+        .filter(|k| !expected.files.contains_key(*k))
+        .cloned()
+        .collect();
+    diff
+}
+
+// For a copy about to replace its source: fail unless the folder `copy` holds
+// exactly the files in `expected`.
+pub(crate) fn ensure_matches(expected: &TreeSums, copy: &Path) -> Result<()> {
+    let actual = hash_tree(copy, expected.algorithm)?;
+    ensure_same(expected, &actual, copy)
+}
+
+// Same for a tarball, which may still be under its temporary name.
+pub(crate) fn ensure_tarball_matches(
+    expected: &TreeSums,
+    tarball: &Path,
+    compression: Compression,
+) -> Result<()> {
+    let actual = hash_tarball(tarball, compression, expected.algorithm)?;
+    ensure_same(expected, &actual, tarball)
+}
+
+fn ensure_same(expected: &TreeSums, actual: &TreeSums, copy: &Path) -> Result<()> {
+    let diff = compare(expected, actual);
+    if !diff.is_clean() {
+        bail!(
+            "'{}' does not match the source's checksums ({})",
+            copy.display(),
+            diff.summary()
+        );
+    }
+    Ok(())
+}
+
+fn sums_path(archived: &Path) -> Option<PathBuf> {
+    let bucket_dir = archived.parent()?;
+    let name = archived.file_name()?;
+    let mut file = name.to_os_string();
+    file.push(".json");
+    Some(bucket_dir.join(CHECKSUM_DIR).join(file))
+}
+
+pub fn load(archived: &Path) -> Result<Option<TreeSums>> {
+    let Some(path) = sums_path(archived) else {
+        return Ok(None);
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(t) => t,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read: {}", path.display())),
+    };
+    serde_json::from_str(&text)
+        .map(Some)
+        .with_context(|| format!("Invalid checksum file: {}", path.display()))
+}
+
+// Written like the manifest: to a temp file, then renamed into place.
+pub fn save(archived: &Path, sums: &TreeSums) -> Result<()> {
+    let Some(path) = sums_path(archived) else {
+        return Ok(());
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create dir: {}", dir.display()))?;
+    }
+    let mut tmp = path.clone().into_os_string();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    fs::write(&tmp, serde_json::to_string(sums)? + "\n")
+        .with_context(|| format!("Failed to write: {}", tmp.display()))?;
+    fs::rename(&tmp, &path).with_context(|| format!("Failed to write: {}", path.display()))
+}
+
+// Forget the checksums of a project that left the archive (or whose name is
+// being reused by one archived without them).
+pub fn remove(archived: &Path) -> Result<()> {
+    let Some(path) = sums_path(archived) else {
+        return Ok(());
+    };
+    match fs::remove_file(&path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e).with_context(|| format!("Failed to remove: {}", path.display())),
+    }
+}
+
+// Send the checksums of a project leaving the archive to the system bin, and
+// record that in `run`: undoing the run then brings them back with the project.
+pub fn trash(archived: &Path, run: &mut journal::Run) -> Result<()> {
+    let Some(path) = sums_path(archived).filter(|p| p.is_file()) else {
+        return Ok(());
+    };
+    trash::delete(&path)
+        .with_context(|| format!("Failed to move '{}' to trash", path.display()))?;
+    run.record_trash(&path);
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    // Some files are missing, modified or unexpected.
+    Damaged,
+    // Checksums were recorded but the project itself is gone.
+    Missing,
+    // Archived without checksums; nothing to compare against.
+    Unchecked,
+}
+
+impl Status {
+    pub fn label(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Damaged => "damaged",
+            Status::Missing => "missing",
+            Status::Unchecked => "unchecked",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Verified {
+    pub bucket: String,
+    pub name: String,
+    pub path: PathBuf,
+    pub status: Status,
+    pub algorithm: Option<Algorithm>,
+    pub file_count: usize,
+    pub diff: Diff,
+    // Why the project couldn't be checked, when it couldn't be read.
+    pub error: Option<String>,
+}
+
+impl Verified {
+    pub fn failed(&self) -> bool {
+        matches!(self.status, Status::Damaged | Status::Missing)
+    }
+}
+
+// Re-hash every project in the manifests under `dir` (an archive root or a
+// single bucket) and compare it with its recorded checksums. Projects that
+// were restored or put back by an undo have no checksums left and are skipped.
+// A manifest, checksum file or archive that can't be read is reported as
+// damaged, and the rest are still checked.
+pub fn verify_archive(dir: &Path) -> Result<Vec<Verified>> {
+    let is_bucket = dir
+        .file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(manifest::is_bucket_name)
+        && dir.join(manifest::MANIFEST_FILE).is_file();
+    let buckets = if is_bucket {
+        vec![dir.to_path_buf()]
+    } else {
+        manifest::buckets(dir)?
+    };
+
+    let mut verified = Vec::new();
+    for bucket_dir in buckets {
+        let bucket = bucket_dir
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut entries = match Manifest::load(&bucket_dir) {
+            Ok(m) => m.entries,
+            Err(e) => {
+                verified.push(Verified {
+                    bucket,
+                    name: manifest::MANIFEST_FILE.to_string(),
+                    path: bucket_dir.join(manifest::MANIFEST_FILE),
+                    status: Status::Damaged,
+                    algorithm: None,
+                    file_count: 0,
+                    diff: Diff::default(),
+                    error: Some(format!("{:#}", e)),
+                });
+                continue;
+            }
+        };
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        for entry in entries {
+            let path = bucket_dir.join(&entry.name);
+            let present = fs::symlink_metadata(&path).is_ok();
+            let sums = load(&path);
+
+            let mut error = None;
+            let (status, diff) = match (&sums, present) {
+                (Ok(None), false) => continue,
+                (Err(e), _) => {
+                    error = Some(format!("{:#}", e));
+                    (Status::Damaged, Diff::default())
+                }
+                (Ok(None), true) => (Status::Unchecked, Diff::default()),
+                (Ok(Some(_)), false) => (Status::Missing, Diff::default()),
+                (Ok(Some(sums)), true) => match hash_archived(&path, sums.algorithm) {
+                    Ok(actual) => {
+                        let diff = compare(sums, &actual);
+                        let status = if diff.is_clean() {
+                            Status::Ok
+                        } else {
+                            Status::Damaged
+                        };
+                        (status, diff)
+                    }
+                    Err(e) => {
+                        error = Some(format!("{:#}", e));
+                        (Status::Damaged, Diff::default())
+                    }
+                },
+            };
+
+            let sums = sums.ok().flatten();
+            verified.push(Verified {
+                bucket: bucket.clone(),
+                name: entry.name,
+                path,
+                status,
+                algorithm: sums.as_ref().map(|s| s.algorithm),
+                file_count: sums.as_ref().map_or(0, |s| s.files.len()),
+                diff,
+                error,
+            });
+        }
+    }
+    Ok(verified)
+}
+
+pub fn print_verify(verified: &[Verified]) {
+    if verified.is_empty() {
+        println!("Nothing archived.");
+        return;
+    }
+
+    for v in verified {
+        let label = format!("{}/{}", v.bucket, v.name);
+        match v.status {
+            Status::Ok => println!(
+                "  ok         {}  ({} files, {})",
+                label,
+                v.file_count,
+                v.algorithm.map_or("?", Algorithm::label)
+            ),
+            Status::Damaged => {
+                match &v.error {
+                    Some(e) => println!("  DAMAGED    {}  ({})", label, e),
+                    None => println!("  DAMAGED    {}  ({})", label, v.diff.summary()),
+                }
+                for f in &v.diff.missing {
+                    println!("               missing:  {}", f);
+                }
+                for f in &v.diff.modified {
+                    println!("               modified: {}", f);
+                }
+                for f in &v.diff.extra {
+                    println!("               extra:    {}", f);
+                }
+            }
+            Status::Missing => {
+                println!("  MISSING    {}  (checksums recorded, project gone)", label)
+            }
+            Status::Unchecked => println!("  unchecked  {}  (archived without --checksum)", label),
+        }
+    }
+
+    let count = |s: Status| verified.iter().filter(|v| v.status == s).count();
+    println!(
+        "\n{} ok, {} damaged, {} missing, {} unchecked.",
+        count(Status::Ok),
+        count(Status::Damaged),
+        count(Status::Missing),
+        count(Status::Unchecked)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A bucket whose manifest lists `names`, all archived from `from`.
+    fn bucket(root: &Path, month: &str, names: &[&str], from: &Path) -> PathBuf {
+        let dir = root.join(month);
+        fs::create_dir_all(&dir).unwrap();
+        let entries: Vec<_> = names
+            .iter()
+            .map(|n| {
+                serde_json::json!({
+                    "name": n,
+                    "original_path": from.join(n),
+                    "archived_at": Local::now(),
+                })
+            })
+            .collect();
+        let text = serde_json::json!({ "entries": entries }).to_string();
+        fs::write(dir.join(manifest::MANIFEST_FILE), text).unwrap();
+        dir
+    }
+
+    fn project(dir: &Path) {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join("main.rs"), "fn main() {}\n").unwrap();
+    }
+
+    #[test]
+    fn verify_keeps_going_past_unreadable_entries() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("archive");
+        let code = tmp.path().join("code");
+        let jan = bucket(&archive, "2026-01", &["a.tar.gz", "b"], &code);
+        let feb = archive.join("2026-02");
+        fs::create_dir_all(&feb).unwrap();
+        fs::write(feb.join(manifest::MANIFEST_FILE), "{ not json").unwrap();
+
+        project(&jan.join("b"));
+        let sums = hash_tree(&jan.join("b"), Algorithm::Blake3).unwrap();
+        save(&jan.join("b"), &sums).unwrap();
+        fs::write(jan.join("a.tar.gz"), "not a tarball").unwrap();
+        save(&jan.join("a.tar.gz"), &sums).unwrap();
+
+        let verified = verify_archive(&archive).unwrap();
+        let status: Vec<_> = verified
+            .iter()
+            .map(|v| (v.name.as_str(), v.status, v.error.is_some()))
+            .collect();
+        assert_eq!(
+            status,
+            [
+                ("a.tar.gz", Status::Damaged, true),
+                ("b", Status::Ok, false),
+                (manifest::MANIFEST_FILE, Status::Damaged, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn verify_reports_a_lost_copy_even_if_the_original_exists() {
+        let tmp = tempfile::tempdir().unwrap();
+        let code = tmp.path().join("code");
+        let jan = bucket(tmp.path(), "2026-01", &["app", "gone"], &code);
+        project(&code.join("app"));
+
+        let sums = hash_tree(&code.join("app"), Algorithm::Sha256).unwrap();
+        save(&jan.join("app"), &sums).unwrap();
+
+        // Checksums without the project are a loss; neither is a restored project.
+        let verified = verify_archive(&jan).unwrap();
+        assert_eq!(verified.len(), 1);
+        assert_eq!(verified[0].name, "app");
+        assert_eq!(verified[0].status, Status::Missing);
+    }
+}
@@ -0,0 +1,219 @@
+use crate::kind::{self, Ecosystem};
+use crate::{ProjectItem, ProjectSize, ScanReport, fmt_bytes, git, journal, mover};
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+
+// Removing the build output of stale projects (`target/`, `node_modules/`, ...)
+// while leaving the projects themselves where they are. The folders go to the
+// system bin like everything else sweeper removes; `--permanent` deletes them
+// outright for when the point is to get the space back now.
+
+#[derive(Debug, Clone)]
+pub struct ArtifactDir {
+    pub path: PathBuf,
+    pub ecosystem: Ecosystem,
+    pub size: ProjectSize,
+    // Why it is kept despite looking like build output.
+    pub skipped: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CleanItem {
+    pub project: ProjectItem,
+    pub ecosystems: Vec<Ecosystem>,
+    pub artifacts: Vec<ArtifactDir>,
+}
+
+impl CleanItem {
+    // What removing its artifact folders frees, on disk.
+    pub fn reclaimable(&self) -> u64 {
+        self.artifacts
+            .iter()
+            .filter(|a| a.skipped.is_none())
+            .map(|a| a.size.disk_bytes)
+            .sum()
+    }
+}
+
+// The artifact folders of every stale project in `report`. Projects without
+// any are left out.
+pub fn plan_clean(report: &ScanReport) -> Vec<CleanItem> {
+    let mut items = Vec::new();
+    for project in &report.stale {
+        let ecosystems = kind::detect(&project.path);
+        if ecosystems.is_empty() {
+            continue;
+        }
+        let is_repo = git::is_repo(&project.path);
+
+        let artifacts: Vec<ArtifactDir> = kind::artifact_dirs(&project.path, &ecosystems)
+            .into_iter()
+            .map(|(ecosystem, path)| {
+                // Checked-in output (a committed `build/`, vendored modules) isn't ours to drop.
+                let skipped = (is_repo && git::tracks_files_in(&project.path, &path))
+                    .then(|| "contains files tracked by git".to_string());
+                ArtifactDir {
+                    size: crate::dir_size(&path),
+                    path,
+                    ecosystem,
+                    skipped,
+                }
+            })
+            .collect();
+
+        if !artifacts.is_empty() {
+            items.push(CleanItem {
+                project: project.clone(),
+                ecosystems,
+                artifacts,
+            });
+        }
+    }
+    items
+}
+
+// Deleted folders can't be undone and so aren't recorded in `run`.
+pub fn apply_clean(items: &[CleanItem], permanent: bool, run: &mut journal::Run) -> Result<()> {
+    for dir in items
+        .iter()
+        .flat_map(|i| &i.artifacts)
+        .filter(|a| a.skipped.is_none())
+    {
+        if permanent {
+            mover::remove_path(&dir.path)
+                .with_context(|| format!("Failed to remove: {}", dir.path.display()))?;
+        } else {
+            trash::delete(&dir.path)
+                .with_context(|| format!("Failed to move '{}' to trash", dir.path.display()))?;
+            run.record_trash(&dir.path);
+        }
+    }
+    Ok(())
+}
+
+pub fn print_clean(items: &[CleanItem]) {
+    if items.is_empty() {
+        println!("No build artifacts found in stale projects.");
+        return;
+    }
+
+    for item in items {
+        let kinds: Vec<&str> = item.ecosystems.iter().map(|e| e.label()).collect();
+        println!(
+            "{}  [{}]  {} reclaimable",
+            item.project.path.display(),
+            kinds.join(", "),
+            fmt_bytes(item.reclaimable())
+        );
+        for a in &item.artifacts {
+            let rel = a
+                .path
+                .strip_prefix(&item.project.path)
+                .unwrap_or(&a.path)
+                .display();
+            match &a.skipped {
+                None => println!("  - {:<24} {:>10}", rel, fmt_bytes(a.size.disk_bytes)),
+                Some(why) => println!("  - {:<24} {:>10}  kept: {}", rel, "", why),
+            }
+        }
+    }
+
+    let total: u64 = items.iter().map(CleanItem::reclaimable).sum();
+    println!(
+        "\n{} project(s), {} reclaimable in total.",
+        items.len(),
+        fmt_bytes(total)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ActivitySource, ProjectKind, SortKey};
+    use std::fs;
+    use std::path::Path;
+    use std::process::Command;
+    use std::time::SystemTime;
+
+    fn report(project: &Path) -> ScanReport {
+        ScanReport {
+            roots: Vec::new(),
+            stale: vec![ProjectItem {
+                path: project.to_path_buf(),
+                kind: ProjectKind::Java,
+                vcs: None,
+                last_modified: SystemTime::UNIX_EPOCH,
+                activity_source: ActivitySource::Mtime,
+                size: None,
+            }],
+            fresh: Vec::new(),
+            excluded: Vec::new(),
+            sort: SortKey::Age,
+        }
+    }
+
+    // A Gradle project with a `build/` and a `.gradle/` cache.
+    fn gradle(dir: &Path) -> (PathBuf, PathBuf) {
+        let (build, cache) = (dir.join("build"), dir.join(".gradle"));
+        fs::create_dir_all(&build).unwrap();
+        fs::create_dir_all(&cache).unwrap();
+        fs::write(dir.join("build.gradle"), "").unwrap();
+        fs::write(build.join("out.jar"), "jar").unwrap();
+        fs::write(cache.join("state.bin"), "state").unwrap();
+        (build, cache)
+    }
+
+    fn git(dir: &Path, args: &[&str]) {
+        let ok = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=t", "-c", "user.email=t@example.com"])
+            .args(args)
+            .status()
+            .unwrap()
+            .success();
+        assert!(ok, "git {:?} failed", args);
+    }
+
+    #[test]
+    fn folders_with_tracked_files_are_kept() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (build, cache) = gradle(tmp.path());
+        git(tmp.path(), &["init", "-q"]);
+        git(tmp.path(), &["add", "build.gradle", "build/out.jar"]);
+        git(tmp.path(), &["commit", "-q", "-m", "vendored build"]);
+
+        let items = plan_clean(&report(tmp.path()));
+        assert_eq!(items.len(), 1);
+        let artifacts: Vec<_> = items[0]
+            .artifacts
+            .iter()
+            .map(|a| (a.path.clone(), a.skipped.is_some()))
+            .collect();
+        assert_eq!(artifacts, [(build, true), (cache, false)]);
+        assert_eq!(
+            items[0].reclaimable(),
+            items[0].artifacts[1].size.disk_bytes
+        );
+    }
+
+    #[test]
+    fn cleaning_trashes_by_default_and_deletes_when_permanent() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (build, cache) = gradle(tmp.path());
+        let items = plan_clean(&report(tmp.path()));
+        assert!(items[0].artifacts.iter().all(|a| a.skipped.is_none()));
+
+        let mut run = journal::Run::new("clean");
+        apply_clean(&items, false, &mut run).unwrap();
+        assert!(!build.exists() && !cache.exists());
+        assert_eq!(run.trashed, [build.clone(), cache.clone()]);
+
+        gradle(tmp.path());
+        let mut run = journal::Run::new("clean");
+        apply_clean(&items, true, &mut run).unwrap();
+        assert!(!build.exists() && !cache.exists());
+        assert!(run.is_empty());
+        assert!(tmp.path().join("build.gradle").is_file());
+    }
+}
@@ -0,0 +1,508 @@
+use crate::checksum::{self, TreeSums};
+use crate::mover;
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use walkdir::WalkDir;
+
+// Packing a project folder into a compressed tarball and back. The tarball
+// holds a single top-level folder named after the project, like a hand-made
+// `tar -caf name.tar.zst name` would.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    /// Move the folder as-is
+    #[default]
+    None,
+    /// .tar.zst
+    Zstd,
+    /// .tar.gz
+    Gzip,
+    /// .tar.xz
+    Xz,
+}
+
+impl Compression {
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Zstd => Some("tar.zst"),
+            Compression::Gzip => Some("tar.gz"),
+            Compression::Xz => Some("tar.xz"),
+        }
+    }
+
+    // The compression a tarball name implies, if any.
+    pub fn from_path(path: &Path) -> Option<Compression> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        [Compression::Zstd, Compression::Gzip, Compression::Xz]
+            .into_iter()
+            .find(|c| {
+                c.extension()
+                    .is_some_and(|ext| name.ends_with(&format!(".{}", ext)))
+            })
+    }
+
+    // `name.tar.zst` -> `name`
+    pub fn strip_extension(self, name: &str) -> String {
+        match self.extension() {
+            Some(ext) => name
+                .strip_suffix(&format!(".{}", ext))
+                .unwrap_or(name)
+                .to_string(),
+            None => name.to_string(),
+        }
+    }
+
+    fn writer(self, file: File) -> Result<Box<dyn Finish>> {
+        let file = BufWriter::new(file);
+        Ok(match self {
+            Compression::None => bail!("No compression selected"),
+            Compression::Zstd => {
+                Box::new(zstd::Encoder::new(file, zstd::DEFAULT_COMPRESSION_LEVEL)?)
+            }
+            Compression::Gzip => Box::new(flate2::write::GzEncoder::new(
+                file,
+                flate2::Compression::default(),
+            )),
+            Compression::Xz => Box::new(xz2::write::XzEncoder::new(file, 6)),
+        })
+    }
+
+    pub(crate) fn reader(self, path: &Path) -> Result<Box<dyn Read>> {
+        let file = BufReader::new(
+            File::open(path).with_context(|| format!("Failed to open: {}", path.display()))?,
+        );
+        Ok(match self {
+            Compression::None => bail!("No compression selected"),
+            Compression::Zstd => Box::new(zstd::Decoder::with_buffer(file)?),
+            Compression::Gzip => Box::new(flate2::bufread::GzDecoder::new(file)),
+            Compression::Xz => Box::new(xz2::bufread::XzDecoder::new(file)),
+        })
+    }
+}
+
+// Encoders need an explicit finish to write their trailer; dropping one would
+// silently produce a truncated stream.
+trait Finish: Write {
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+impl<W: Write> Finish for zstd::Encoder<'static, W> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        (*self).finish()?.flush()
+    }
+}
+
+impl<W: Write> Finish for flate2::write::GzEncoder<W> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        (*self).finish()?.flush()
+    }
+}
+
+impl<W: Write> Finish for xz2::write::XzEncoder<W> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        (*self).finish()?.flush()
+    }
+}
+
+// Stream `from` into the tarball `to`, read it back to check it against the
+// source (and against `expected`, when given), and only then remove the source.
+pub fn pack(
+    from: &Path,
+    to: &Path,
+    compression: Compression,
+    expected: Option<&TreeSums>,
+) -> Result<()> {
+    if fs::symlink_metadata(to).is_ok() {
+        bail!("Destination already exists: {}", to.display());
+    }
+    let name = from
+        .file_name()
+        .with_context(|| format!("Cannot archive: {}", from.display()))?;
+
+    // Written under a temporary name so an interrupted run never leaves
+    // something that looks like a finished archive.
+    let partial = partial_path(to);
+    let result = write_tar(from, name.as_ref(), &partial, compression)
+        .and_then(|_| verify(&partial, from, compression))
+        .and_then(|_| match expected {
+            Some(sums) => checksum::ensure_tarball_matches(sums, &partial, compression),
+            None => Ok(()),
+        })
+        .and_then(|_| {
+            mover::rename_new(&partial, to)
+                .with_context(|| format!("Failed to rename into place: {}", to.display()))
+        });
+    if let Err(e) = result {
+        let _ = fs::remove_file(&partial);
+        return Err(e.context(format!(
+            "Failed to pack '{}' -> '{}'",
+            from.display(),
+            to.display()
+        )));
+    }
+
+    mover::remove_path(from).with_context(|| {
+        format!(
+            "Packed into '{}' but failed to remove source '{}'",
+            to.display(),
+            from.display()
+        )
+    })
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut p = path.as_os_str().to_owned();
+    p.push(".partial");
+    PathBuf::from(p)
+}
+
+fn write_tar(from: &Path, name: &Path, to: &Path, compression: Compression) -> Result<()> {
+    let file = File::create(to).with_context(|| format!("Failed to create: {}", to.display()))?;
+    let mut builder = tar::Builder::new(compression.writer(file)?);
+    // Keep symlinks as symlinks, and full modes, owners and mtimes.
+    builder.follow_symlinks(false);
+    builder.mode(tar::HeaderMode::Complete);
+    builder
+        .append_dir_all(name, from)
+        .with_context(|| format!("Failed to add to archive: {}", from.display()))?;
+    builder.into_inner()?.finish()?;
+
+    File::open(to)?.sync_all()?;
+    Ok(())
+}
+
+// Every entry of the tarball must match the source: same set of paths, types,
+// symlink targets, permissions, mtimes and file contents.
+pub fn verify(archive: &Path, source: &Path, compression: Compression) -> Result<()> {
+    let mut tar = tar::Archive::new(compression.reader(archive)?);
+    let mut count = 0usize;
+
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let rel = strip_top(&entry.path()?)?;
+        let src = source.join(&rel);
+        let meta = fs::symlink_metadata(&src)
+            .with_context(|| format!("In archive but not in source: {}", src.display()))?;
+        let header = entry.header();
+        let kind = header.entry_type();
+        count += 1;
+
+        let same_type = (kind.is_dir() && meta.is_dir())
+            || (kind.is_symlink() && meta.file_type().is_symlink())
+            || (kind.is_file() && meta.is_file())
+            || !(meta.is_dir() || meta.is_file() || meta.file_type().is_symlink());
+        if !same_type {
+            bail!("File type mismatch in archive: {}", src.display());
+        }
+
+        if kind.is_symlink() {
+            if entry.link_name()?.as_deref() != Some(fs::read_link(&src)?.as_path()) {
+                bail!("Symlink target mismatch in archive: {}", src.display());
+            }
+            continue;
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            if header.mode()? & 0o7777 != meta.mode() & 0o7777 {
+                bail!("Permission mismatch in archive: {}", src.display());
+            }
+            if header.mtime()? as i64 != meta.mtime() {
+                bail!("Modification time mismatch in archive: {}", src.display());
+            }
+        }
+
+        if kind.is_file() && !same_bytes(&mut entry, &src)? {
+            bail!("Content mismatch in archive: {}", src.display());
+        }
+    }
+
+    let expected = WalkDir::new(source).follow_links(false).into_iter().count();
+    if count != expected {
+        bail!(
+            "Entry count mismatch in archive: {} (expected {}, found {})",
+            archive.display(),
+            expected,
+            count
+        );
+    }
+    Ok(())
+}
+
+// `name/sub/file` -> `sub/file`; refuses anything that would escape.
+pub(crate) fn strip_top(path: &Path) -> Result<PathBuf> {
+    let mut parts = path.components();
+    parts.next();
+    let rest: PathBuf = parts.collect();
+    if rest
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        bail!("Unsafe path in archive: {}", path.display());
+    }
+    Ok(rest)
+}
+
+fn same_bytes(entry: &mut impl Read, path: &Path) -> Result<bool> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut a = vec![0u8; 64 * 1024];
+    let mut b = vec![0u8; 64 * 1024];
+    loop {
+        let n = read_full(entry, &mut a)?;
+        if n != read_full(&mut file, &mut b)? || a[..n] != b[..n] {
+            return Ok(false);
+        }
+        if n == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+fn read_full(r: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match r.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+// Unpack the tarball `from` so that its top-level folder becomes `to`, then
+// remove the tarball.
+pub fn unpack(from: &Path, to: &Path, compression: Compression) -> Result<()> {
+    if fs::symlink_metadata(to).is_ok() {
+        bail!("Destination already exists: {}", to.display());
+    }
+    let parent = to
+        .parent()
+        .with_context(|| format!("Cannot restore to: {}", to.display()))?;
+
+    // Unpack next to the destination first so nothing half-extracted ever sits
+    // at `to`.
+    let staging = parent.join(format!(
+        ".sweeper-unpack-{}",
+        to.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    if fs::symlink_metadata(&staging).is_ok() {
+        bail!("Leftover from an earlier restore: {}", staging.display());
+    }
+
+    let result = (|| -> Result<()> {
+        fs::create_dir(&staging)
+            .with_context(|| format!("Failed to create dir: {}", staging.display()))?;
+        let mut tar = tar::Archive::new(compression.reader(from)?);
+        tar.set_preserve_permissions(true);
+        tar.set_preserve_mtime(true);
+        // Folders are applied after everything else, innermost first, as
+        // `tar::Archive::unpack` does: a read-only one would refuse its own
+        // contents, and writing those would bump its mtime again.
+        let mut dirs = Vec::new();
+        for entry in tar
+            .entries()
+            .with_context(|| format!("Failed to unpack: {}", from.display()))?
+        {
+            let mut entry = entry?;
+            if entry.header().entry_type().is_dir() {
+                dirs.push(entry);
+                continue;
+            }
+            entry
+                .unpack_in(&staging)
+                .with_context(|| format!("Failed to unpack: {}", from.display()))?;
+        }
+        dirs.sort_by(|a, b| b.path_bytes().cmp(&a.path_bytes()));
+        for mut dir in dirs {
+            let path = staging.join(dir.path()?);
+            let mtime = dir.header().mtime()?;
+            if dir
+                .unpack_in(&staging)
+                .with_context(|| format!("Failed to unpack: {}", from.display()))?
+            {
+                let time = filetime::FileTime::from_unix_time(mtime as i64, 0);
+                filetime::set_file_mtime(&path, time)?;
+            }
+        }
+
+        let mut top = fs::read_dir(&staging)?.collect::<io::Result<Vec<_>>>()?;
+        if top.len() != 1 {
+            bail!(
+                "Expected a single top-level folder in {}, found {}",
+                from.display(),
+                top.len()
+            );
+        }
+        let top = top.remove(0).path();
+        move_out(&top, to)
+            .with_context(|| format!("Failed to move '{}' -> '{}'", top.display(), to.display()))
+    })();
+
+    let cleanup = fs::remove_dir_all(&staging);
+    match (result, cleanup) {
+        (Ok(()), Ok(())) => {}
+        (Ok(()), Err(e)) => eprintln!(
+            "Warning: failed to remove leftover '{}': {}",
+            staging.display(),
+            e
+        ),
+        (Err(e), Ok(())) => return Err(e),
+        (Err(e), Err(cleanup)) => {
+            return Err(e.context(format!(
+                "Failed to unpack, and failed to remove '{}': {}",
+                staging.display(),
+                cleanup
+            )));
+        }
+    }
+
+    fs::remove_file(from).with_context(|| {
+        format!(
+            "Unpacked to '{}' but failed to remove '{}'",
+            to.display(),
+            from.display()
+        )
+    })
+}
+
+// Moving a folder to another parent rewrites its `..` entry, which a read-only
+// folder refuses; it is made writable for the move only.
+#[cfg(unix)]
+fn move_out(top: &Path, to: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let perm = fs::symlink_metadata(top)?.permissions();
+    if perm.mode() & 0o200 != 0 {
+        return mover::rename_new(top, to);
+    }
+    fs::set_permissions(top, fs::Permissions::from_mode(perm.mode() | 0o200))?;
+    mover::rename_new(top, to)?;
+    fs::set_permissions(to, perm)
+}
+
+#[cfg(not(unix))]
+fn move_out(top: &Path, to: &Path) -> io::Result<()> {
+    mover::rename_new(top, to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use filetime::FileTime;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn mtime(path: &Path) -> FileTime {
+        FileTime::from_last_modification_time(&fs::symlink_metadata(path).unwrap())
+    }
+
+    #[test]
+    fn pack_then_unpack_restores_the_tree() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = tmp.path().join("app");
+        write(&project.join("src/main.rs"), "fn main() {}\n");
+        write(&project.join("README"), "hello\n");
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("README", project.join("link")).unwrap();
+        let old = FileTime::from_unix_time(1_600_000_000, 0);
+        filetime::set_file_mtime(project.join("README"), old).unwrap();
+        filetime::set_file_mtime(project.join("src"), old).unwrap();
+
+        for compression in [Compression::Zstd, Compression::Gzip, Compression::Xz] {
+            let tarball = tmp
+                .path()
+                .join(format!("app.{}", compression.extension().unwrap()));
+            pack(&project, &tarball, compression, None).unwrap();
+            assert!(!project.exists());
+            assert!(!partial_path(&tarball).exists());
+
+            unpack(&tarball, &project, compression).unwrap();
+            assert!(!tarball.exists());
+            assert_eq!(
+                fs::read_to_string(project.join("src/main.rs")).unwrap(),
+                "fn main() {}\n"
+            );
+            assert_eq!(mtime(&project.join("README")), old);
+            assert_eq!(mtime(&project.join("src")), old);
+            #[cfg(unix)]
+            assert_eq!(
+                fs::read_link(project.join("link")).unwrap(),
+                Path::new("README")
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unpack_applies_read_only_folder_modes_last() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let project = tmp.path().join("app");
+        write(&project.join("docs/guide.md"), "# Guide\n");
+        let old = FileTime::from_unix_time(1_600_000_000, 0);
+        let read_only = fs::Permissions::from_mode(0o555);
+        for dir in [project.join("docs"), project.clone()] {
+            fs::set_permissions(&dir, read_only.clone()).unwrap();
+            filetime::set_file_mtime(&dir, old).unwrap();
+        }
+
+        let tarball = tmp.path().join("app.tar.zst");
+        write_tar(&project, Path::new("app"), &tarball, Compression::Zstd).unwrap();
+        verify(&tarball, &project, Compression::Zstd).unwrap();
+
+        let restored = tmp.path().join("restored");
+        let result = unpack(&tarball, &restored, Compression::Zstd);
+
+        // Let the tempdir clean up after us, whatever happened.
+        let mode = |p: &Path| {
+            fs::metadata(p)
+                .map(|m| m.permissions().mode() & 0o7777)
+                .ok()
+        };
+        let modes = [mode(&restored), mode(&restored.join("docs"))];
+        let times = [restored.clone(), restored.join("docs")]
+            .map(|p| fs::metadata(&p).is_ok().then(|| mtime(&p)));
+        for dir in [&project, &restored] {
+            for d in [dir.clone(), dir.join("docs")] {
+                let _ = fs::set_permissions(&d, fs::Permissions::from_mode(0o755));
+            }
+        }
+
+        result.unwrap();
+        assert_eq!(modes, [Some(0o555), Some(0o555)]);
+        assert_eq!(times, [Some(old), Some(old)]);
+        assert_eq!(
+            fs::read_to_string(restored.join("docs/guide.md")).unwrap(),
+            "# Guide\n"
+        );
+        assert!(!tmp.path().join(".sweeper-unpack-restored").exists());
+    }
+
+    #[test]
+    fn pack_keeps_source_when_checksums_disagree() {
+        let tmp = tempfile::tempdir().unwrap();
+        let project = tmp.path().join("app");
+        write(&project.join("data.txt"), "as archived\n");
+        let mut sums = checksum::hash_tree(&project, checksum::Algorithm::Blake3).unwrap();
+        sums.files.get_mut("data.txt").unwrap().size += 1;
+
+        let tarball = tmp.path().join("app.tar.gz");
+        assert!(pack(&project, &tarball, Compression::Gzip, Some(&sums)).is_err());
+        assert!(!tarball.exists());
+        assert!(!partial_path(&tarball).exists());
+        assert_eq!(
+            fs::read_to_string(project.join("data.txt")).unwrap(),
+            "as archived\n"
+        );
+    }
+}
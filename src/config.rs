@@ -0,0 +1,358 @@
+use crate::checksum::Algorithm;
+use crate::compress::Compression;
+use crate::rules::OrganizeRule;
+use crate::{ActivityMode, Depth, SortKey};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+// Settings come from, lowest priority first: built-in defaults,
+// ~/.config/sweeper/config.toml, a `.sweeper.toml` in the root being worked on,
+// each followed by the selected `[profiles.<name>]` table of that file.
+// Command-line flags override all of them (applied by the caller).
+
+pub const ROOT_FILE_NAME: &str = ".sweeper.toml";
+
+// Keys holding paths. Relative values are resolved against the file they're in.
+const PATH_KEYS: &[&str] = &["roots", "archive.dest"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    // Roots used when a command is given no PATH.
+    pub roots: Vec<PathBuf>,
+    pub activity: ActivityMode,
+    pub depth: Depth,
+    pub early_exit: bool,
+    pub sizes: bool,
+    // Look for projects below the root's subfolders, down to `discover_depth`.
+    pub discover: bool,
+    pub discover_depth: usize,
+    // Keep a scan index under the cache dir and reuse it on the next scan.
+    pub cache: bool,
+    pub sort: SortKey,
+    // Skip the built-in `DEFAULT_IGNORED_DIRS` when measuring activity.
+    pub default_ignores: bool,
+    // Extra directory names to skip, on top of the built-in ones.
+    pub ignore_dirs: Vec<String>,
+    pub scan: ThresholdSettings,
+    pub archive: ArchiveSettings,
+    pub delete: ThresholdSettings,
+    pub clean: ThresholdSettings,
+    pub organize: OrganizeSettings,
+    pub watch: WatchSettings,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThresholdSettings {
+    pub older_than: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArchiveSettings {
+    pub older_than: u64,
+    pub dest: Option<PathBuf>,
+    pub compress: Compression,
+    pub checksum: Option<Algorithm>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OrganizeSettings {
+    // Classify by file content (magic bytes) as well as by extension.
+    pub sniff: bool,
+    // Leave a file in place when an identical copy is already at its target.
+    pub skip_identical: bool,
+    pub rules: Vec<OrganizeRule>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WatchSettings {
+    // Seconds a new file's size must stay unchanged before it is organized.
+    pub settle_secs: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            roots: Vec::new(),
+            activity: ActivityMode::Mtime,
+            depth: Depth::Unlimited,
+            early_exit: true,
+            sizes: true,
+            discover: false,
+            discover_depth: 4,
+            cache: true,
+            sort: SortKey::Age,
+            default_ignores: true,
+            ignore_dirs: Vec::new(),
+            scan: ThresholdSettings { older_than: 30 },
+            archive: ArchiveSettings {
+                older_than: 30,
+                dest: None,
+                compress: Compression::None,
+                checksum: None,
+            },
+            delete: ThresholdSettings { older_than: 90 },
+            clean: ThresholdSettings { older_than: 30 },
+            organize: OrganizeSettings::default(),
+            watch: WatchSettings::default(),
+        }
+    }
+}
+
+impl Default for ThresholdSettings {
+    fn default() -> Self {
+        ThresholdSettings { older_than: 30 }
+    }
+}
+
+impl Default for WatchSettings {
+    fn default() -> Self {
+        WatchSettings { settle_secs: 5 }
+    }
+}
+
+impl Default for ArchiveSettings {
+    fn default() -> Self {
+        ArchiveSettings {
+            older_than: 30,
+            dest: None,
+            compress: Compression::None,
+            checksum: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    File(PathBuf),
+    Profile { name: String, file: PathBuf },
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::Profile { name, file } => {
+                write!(f, "profile '{}' in {}", name, file.display())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub settings: Settings,
+    // Flattened `section.key` -> (value, where it came from), for `config show`.
+    pub entries: BTreeMap<String, (Value, Source)>,
+    // Files that were actually read, in the order they were applied.
+    pub files: Vec<PathBuf>,
+}
+
+pub fn global_config_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|h| h.join(".config")))
+        .map(|dir| dir.join("sweeper").join("config.toml"))
+}
+
+// Load the merged configuration. `root` is the folder a command works on; its
+// `.sweeper.toml`, if any, is layered over the global file.
+pub fn load(profile: Option<&str>, root: Option<&Path>) -> Result<Config> {
+    let defaults = Table::try_from(Settings::default()).context("Failed to encode defaults")?;
+
+    let mut entries = BTreeMap::new();
+    flatten(&defaults, "", &Source::Default, None, &mut entries);
+
+    let mut files = Vec::new();
+    let mut profile_found = false;
+
+    let candidates = [global_config_path(), root.map(|r| r.join(ROOT_FILE_NAME))];
+    for path in candidates.into_iter().flatten() {
+        let Some(mut table) = read_file(&path)? else {
+            continue;
+        };
+        let base_dir = path.parent().map(Path::to_path_buf);
+
+        let profiles = match table.remove("profiles") {
+            Some(Value::Table(t)) => t,
+            Some(_) => bail!("{}: `profiles` must be a table", path.display()),
+            None => Table::new(),
+        };
+
+        validate(&table, &path, None)?;
+        flatten(
+            &table,
+            "",
+            &Source::File(path.clone()),
+            base_dir.as_deref(),
+            &mut entries,
+        );
+
+        if let Some(name) = profile
+            && let Some(p) = profiles.get(name)
+        {
+            let Value::Table(p) = p else {
+                bail!("{}: profile '{}' must be a table", path.display(), name);
+            };
+            validate(p, &path, Some(name))?;
+            let source = Source::Profile {
+                name: name.to_string(),
+                file: path.clone(),
+            };
+            flatten(p, "", &source, base_dir.as_deref(), &mut entries);
+            profile_found = true;
+        }
+
+        files.push(path);
+    }
+
+    if let Some(name) = profile
+        && !profile_found
+    {
+        bail!("Profile '{}' is not defined in any config file", name);
+    }
+
+    let settings = unflatten(&entries)
+        .try_into()
+        .context("Invalid merged configuration")?;
+
+    Ok(Config {
+        settings,
+        entries,
+        files,
+    })
+}
+
+fn read_file(path: &Path) -> Result<Option<Table>> {
+    let text = match fs::read_to_string(path) {
+        Ok(t) => t,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read config: {}", path.display()));
+        }
+    };
+    let table: Table =
+        toml::from_str(&text).with_context(|| format!("Invalid config: {}", path.display()))?;
+    Ok(Some(table))
+}
+
+// Catch typos and wrong types with a useful location before merging anything.
+fn validate(table: &Table, path: &Path, profile: Option<&str>) -> Result<()> {
+    Settings::deserialize(table.clone())
+        .map(|_| ())
+        .with_context(|| match profile {
+            Some(name) => format!("Invalid profile '{}' in {}", name, path.display()),
+            None => format!("Invalid config: {}", path.display()),
+        })
+}
+
+// Tables become dotted keys; everything else (including arrays of tables such
+// as `organize.rules`) is a single value that later layers replace wholesale.
+fn flatten(
+    table: &Table,
+    prefix: &str,
+    source: &Source,
+    base_dir: Option<&Path>,
+    out: &mut BTreeMap<String, (Value, Source)>,
+) {
+    for (key, value) in table {
+        let full = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            Value::Table(t) => flatten(t, &full, source, base_dir, out),
+            _ => {
+                let value = if PATH_KEYS.contains(&full.as_str()) {
+                    resolve_paths(value, base_dir)
+                } else {
+                    value.clone()
+                };
+                out.insert(full, (value, source.clone()));
+            }
+        }
+    }
+}
+
+fn unflatten(entries: &BTreeMap<String, (Value, Source)>) -> Table {
+    let mut root = Table::new();
+    for (key, (value, _)) in entries {
+        let mut parts: Vec<&str> = key.split('.').collect();
+        let last = parts.pop().unwrap_or_default();
+        let mut table = &mut root;
+        for part in parts {
+            table = match table
+                .entry(part.to_string())
+                .or_insert_with(|| Value::Table(Table::new()))
+            {
+                Value::Table(t) => t,
+                _ => unreachable!("config keys never nest under a scalar"),
+            };
+        }
+        table.insert(last.to_string(), value.clone());
+    }
+    root
+}
+
+fn resolve_paths(value: &Value, base_dir: Option<&Path>) -> Value {
+    match value {
+        Value::String(s) => {
+            let path = expand_tilde(s);
+            let path = match base_dir {
+                Some(base) if path.is_relative() => base.join(path),
+                _ => path,
+            };
+            Value::String(path.to_string_lossy().to_string())
+        }
+        Value::Array(items) => {
+            Value::Array(items.iter().map(|v| resolve_paths(v, base_dir)).collect())
+        }
+        other => other.clone(),
+    }
+}
+
+pub fn expand_tilde(s: &str) -> PathBuf {
+    if let Some(rest) = s.strip_prefix("~/")
+        && let Some(home) = dirs::home_dir()
+    {
+        return home.join(rest);
+    }
+    if s == "~"
+        && let Some(home) = dirs::home_dir()
+    {
+        return home;
+    }
+    PathBuf::from(s)
+}
+
+pub fn print_config(config: &Config) {
+    if config.files.is_empty() {
+        println!("No config files found; using built-in defaults.");
+        if let Some(path) = global_config_path() {
+            println!("(global config would be read from {})", path.display());
+        }
+    } else {
+        println!("Config files (lowest priority first):");
+        for f in &config.files {
+            println!("  - {}", f.display());
+        }
+    }
+    println!();
+
+    for (key, (value, source)) in &config.entries {
+        println!("{} = {}    # {}", key, value, source);
+    }
+}
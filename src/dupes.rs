@@ -0,0 +1,476 @@
+use crate::{journal, mover};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+// Duplicate files. Candidates are narrowed down cheaply first: same size, then
+// same hash of the first block, and only then a hash of the whole content.
+
+// How much of each file the partial hash covers.
+const PARTIAL_LEN: u64 = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Keep {
+    Newest,
+    Oldest,
+}
+
+#[derive(Debug, Clone)]
+pub struct DupeFile {
+    pub path: PathBuf,
+    pub modified: SystemTime,
+    // Other names of this very file (hardlinks) under the root. They share its
+    // storage, so they go or get relinked together with it.
+    pub links: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DupeGroup {
+    pub size: u64,
+    pub hash: String,
+    // Oldest first.
+    pub files: Vec<DupeFile>,
+}
+
+// What `find_dupes` found, and the files it had to leave out.
+#[derive(Debug, Clone, Default)]
+pub struct Found {
+    pub groups: Vec<DupeGroup>,
+    pub unreadable: Vec<Unreadable>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Unreadable {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+impl DupeFile {
+    pub fn names(&self) -> impl Iterator<Item = &PathBuf> {
+        std::iter::once(&self.path).chain(&self.links)
+    }
+}
+
+impl DupeGroup {
+    // Space taken by every copy but one.
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.files.len() as u64 - 1)
+    }
+
+    // The copy to keep, and the ones that go.
+    pub fn split(&self, keep: Keep) -> (&DupeFile, Vec<&DupeFile>) {
+        let idx = match keep {
+            Keep::Oldest => 0,
+            Keep::Newest => self.files.len() - 1,
+        };
+        let rest = self
+            .files
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != idx)
+            .map(|(_, f)| f)
+            .collect();
+        (&self.files[idx], rest)
+    }
+}
+
+// Every group of two or more files under `root` with identical content, the
+// most wasteful first. Empty files don't count, and hardlinks of one another
+// are one file, not duplicates. Folders and files that can't be read are
+// skipped and listed in `Found::unreadable`.
+pub fn find_dupes(root: &Path) -> Result<Found> {
+    let mut by_size: HashMap<u64, Vec<DupeFile>> = HashMap::new();
+    let mut unreadable = Vec::new();
+    // (dev, inode) -> where that file sits in `by_size`.
+    #[cfg(unix)]
+    let mut inodes: HashMap<(u64, u64), usize> = HashMap::new();
+
+    for entry in WalkDir::new(root).follow_links(false) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) if e.depth() == 0 => {
+                return Err(e).with_context(|| format!("Failed to read: {}", root.display()));
+            }
+            Err(e) => {
+                let path = e.path().unwrap_or(root).to_path_buf();
+                unreadable.push(Unreadable {
+                    path,
+                    error: e.to_string(),
+                });
+                continue;
+            }
+        };
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let meta = match entry.metadata() {
+            Ok(meta) => meta,
+            Err(e) => {
+                unreadable.push(Unreadable {
+                    path: entry.into_path(),
+                    error: e.to_string(),
+                });
+                continue;
+            }
+        };
+        if meta.len() == 0 {
+            continue;
+        }
+
+        let same_size = by_size.entry(meta.len()).or_default();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            match inodes.entry((meta.dev(), meta.ino())) {
+                std::collections::hash_map::Entry::Occupied(e) => {
+                    same_size[*e.get()].links.push(entry.into_path());
+                    continue;
+                }
+                std::collections::hash_map::Entry::Vacant(e) => {
+                    e.insert(same_size.len());
+                }
+            }
+        }
+
+        same_size.push(DupeFile {
+            path: entry.into_path(),
+            modified: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            links: Vec::new(),
+        });
+    }
+
+    let mut groups = Vec::new();
+    for (size, files) in by_size {
+        if files.len() < 2 {
+            continue;
+        }
+        let partials = group_by(files, |p| hash_file(p, Some(PARTIAL_LEN)), &mut unreadable);
+        for (partial, files) in partials {
+            // Small files were read completely by the partial hash already.
+            let full = if size <= PARTIAL_LEN {
+                vec![(partial, files)]
+            } else {
+                group_by(files, |p| hash_file(p, None), &mut unreadable)
+            };
+            for (hash, mut files) in full {
+                files.sort_by(|a, b| a.modified.cmp(&b.modified).then(a.path.cmp(&b.path)));
+                groups.push(DupeGroup { size, hash, files });
+            }
+        }
+    }
+
+    groups.sort_by(|a, b| {
+        b.wasted()
+            .cmp(&a.wasted())
+            .then(a.files[0].path.cmp(&b.files[0].path))
+    });
+    unreadable.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(Found { groups, unreadable })
+}
+
+// Split `files` by `key`, keeping only buckets with more than one file.
+// Files that can't be read are moved to `unreadable`.
+fn group_by(
+    files: Vec<DupeFile>,
+    key: impl Fn(&Path) -> io::Result<String>,
+    unreadable: &mut Vec<Unreadable>,
+) -> Vec<(String, Vec<DupeFile>)> {
+    let mut buckets: HashMap<String, Vec<DupeFile>> = HashMap::new();
+    for f in files {
+        match key(&f.path) {
+            Ok(k) => buckets.entry(k).or_default().push(f),
+            Err(e) => unreadable.push(Unreadable {
+                path: f.path,
+                error: e.to_string(),
+            }),
+        }
+    }
+    buckets.into_iter().filter(|(_, v)| v.len() > 1).collect()
+}
+
+fn hash_file(path: &Path, limit: Option<u64>) -> io::Result<String> {
+    let file = File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+    match limit {
+        Some(n) => io::copy(&mut file.take(n), &mut hasher)?,
+        None => io::copy(&mut io::BufReader::new(file), &mut hasher)?,
+    };
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+// Byte-for-byte comparison, for checking one pair without hashing.
+pub fn same_content(a: &Path, b: &Path) -> io::Result<bool> {
+    if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+    let mut ra = io::BufReader::new(File::open(a)?);
+    let mut rb = io::BufReader::new(File::open(b)?);
+    let mut ba = vec![0u8; 64 * 1024];
+    let mut bb = vec![0u8; 64 * 1024];
+    loop {
+        let n = read_full(&mut ra, &mut ba)?;
+        if n != read_full(&mut rb, &mut bb)? || ba[..n] != bb[..n] {
+            return Ok(false);
+        }
+        if n == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+fn read_full(r: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match r.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+// Whether `path` still has the kept file's content. A copy that changed since
+// the scan, or can't be read any more, is added to `skipped` instead.
+fn still_same(kept: &Path, path: &Path, skipped: &mut Vec<String>) -> bool {
+    match same_content(kept, path) {
+        Ok(true) => true,
+        Ok(false) => {
+            skipped.push(format!("{}: changed since the scan", path.display()));
+            false
+        }
+        Err(e) => {
+            skipped.push(format!("{}: {}", path.display(), e));
+            false
+        }
+    }
+}
+
+// Send every copy but the kept one to the system bin. Like `hardlink_dupes`,
+// copies that no longer match the kept file are left alone and reported back.
+pub fn trash_dupes(
+    groups: &[DupeGroup],
+    keep: Keep,
+    run: &mut journal::Run,
+) -> Result<Vec<String>> {
+    let mut skipped = Vec::new();
+    for g in groups {
+        let (kept, rest) = g.split(keep);
+        for path in rest.iter().flat_map(|f| f.names()) {
+            if !still_same(&kept.path, path, &mut skipped) {
+                continue;
+            }
+            trash::delete(path)
+                .with_context(|| format!("Failed to move '{}' to trash", path.display()))?;
+            run.record_trash(path);
+        }
+    }
+    Ok(skipped)
+}
+
+// Replace every copy but the kept one with a hardlink to it. Each copy is
+// compared with the kept file again right before, and one that no longer
+// matches is left alone, as are copies on another filesystem (those can't be
+// linked) and copies whose temporary link name is taken; all are reported
+// back. The copies themselves go to the system bin, so `undo` can put them
+// back.
+pub fn hardlink_dupes(
+    groups: &[DupeGroup],
+    keep: Keep,
+    run: &mut journal::Run,
+) -> Result<Vec<String>> {
+    let mut skipped = Vec::new();
+    for g in groups {
+        let (kept, rest) = g.split(keep);
+        for path in rest.iter().flat_map(|f| f.names()) {
+            if !still_same(&kept.path, path, &mut skipped) {
+                continue;
+            }
+
+            // Link under a temporary name first so the copy is only replaced once
+            // the link exists.
+            let tmp = link_tmp(path);
+
+            match fs::hard_link(&kept.path, &tmp) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+                    skipped.push(format!(
+                        "{}: on a different filesystem than {}",
+                        path.display(),
+                        kept.path.display()
+                    ));
+                    continue;
+                }
+                // Most likely left behind by an interrupted run; not ours to remove.
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    skipped.push(format!(
+                        "{}: '{}' already exists",
+                        path.display(),
+                        tmp.display()
+                    ));
+                    continue;
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!(
+                            "Failed to link '{}' -> '{}'",
+                            tmp.display(),
+                            kept.path.display()
+                        )
+                    });
+                }
+            }
+            if let Err(e) = trash::delete(path) {
+                let _ = fs::remove_file(&tmp);
+                return Err(e)
+                    .with_context(|| format!("Failed to move '{}' to trash", path.display()));
+            }
+            run.record_trash(path);
+            if let Err(e) = mover::rename_new(&tmp, path) {
+                let _ = fs::remove_file(&tmp);
+                return Err(e).with_context(|| format!("Failed to replace: {}", path.display()));
+            }
+            run.record_link(&kept.path, path);
+        }
+    }
+    Ok(skipped)
+}
+
+fn link_tmp(path: &Path) -> PathBuf {
+    let mut tmp = path.as_os_str().to_os_string();
+    tmp.push(".sweeper-link");
+    PathBuf::from(tmp)
+}
+
+pub fn print_dupes(found: &Found, keep: Option<Keep>) {
+    if !found.unreadable.is_empty() {
+        println!("Skipped (unreadable):");
+        for u in &found.unreadable {
+            println!("  - {}  ({})", u.path.display(), u.error);
+        }
+        println!();
+    }
+
+    let groups = &found.groups;
+    if groups.is_empty() {
+        println!("No duplicate files found.");
+        return;
+    }
+
+    for (i, g) in groups.iter().enumerate() {
+        println!(
+            "Group {} ({} copies of {}, {} wasted):",
+            i + 1,
+            g.files.len(),
+            crate::fmt_bytes(g.size),
+            crate::fmt_bytes(g.wasted())
+        );
+        let kept = keep.map(|k| g.split(k).0.path.clone());
+        for f in &g.files {
+            let mark = match &kept {
+                Some(k) if *k == f.path => "keep",
+                Some(_) => "drop",
+                None => "-",
+            };
+            println!(
+                "  {:<4} {}  {}",
+                mark,
+                crate::fmt_time(f.modified),
+                f.path.display()
+            );
+            for link in &f.links {
+                println!("{:24}(also linked as {})", "", link.display());
+            }
+        }
+    }
+
+    let wasted: u64 = groups.iter().map(DupeGroup::wasted).sum();
+    println!(
+        "\n{} duplicate group(s), {} wasted.",
+        groups.len(),
+        crate::fmt_bytes(wasted)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+
+    // Two copies of the same content, `a` older than `b`.
+    fn copies(dir: &Path) -> (PathBuf, PathBuf, Vec<DupeGroup>) {
+        let (a, b) = (dir.join("a.txt"), dir.join("b.txt"));
+        fs::write(&a, "same content").unwrap();
+        fs::write(&b, "same content").unwrap();
+        let old = filetime::FileTime::from_unix_time(1_600_000_000, 0);
+        filetime::set_file_mtime(&a, old).unwrap();
+        let found = find_dupes(dir).unwrap();
+        assert_eq!(found.groups.len(), 1);
+        (a, b, found.groups)
+    }
+
+    #[test]
+    fn a_copy_changed_since_the_scan_is_left_alone() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (a, b, groups) = copies(tmp.path());
+        fs::write(&b, "edited content").unwrap();
+
+        let mut run = journal::Run::new("dupes");
+        let skipped = hardlink_dupes(&groups, Keep::Oldest, &mut run).unwrap();
+        assert_eq!(skipped.len(), 1);
+        assert!(skipped[0].contains("changed since the scan"));
+        let skipped = trash_dupes(&groups, Keep::Oldest, &mut run).unwrap();
+        assert_eq!(skipped.len(), 1);
+
+        assert!(run.is_empty());
+        assert_eq!(fs::read_to_string(&b).unwrap(), "edited content");
+        assert_ne!(
+            fs::metadata(&a).unwrap().ino(),
+            fs::metadata(&b).unwrap().ino()
+        );
+    }
+
+    #[test]
+    fn hardlink_replaces_the_copy_with_the_kept_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (a, b, groups) = copies(tmp.path());
+        let kept_ino = fs::metadata(&a).unwrap().ino();
+
+        let mut run = journal::Run::new("dupes");
+        let skipped = hardlink_dupes(&groups, Keep::Oldest, &mut run).unwrap();
+        assert!(skipped.is_empty());
+
+        assert_eq!(fs::metadata(&a).unwrap().ino(), kept_ino);
+        assert_eq!(fs::metadata(&b).unwrap().ino(), kept_ino);
+        assert_eq!(fs::read_to_string(&b).unwrap(), "same content");
+        assert!(!link_tmp(&b).exists());
+        assert_eq!(run.trashed, [b.as_path()]);
+        assert_eq!(run.linked.len(), 1);
+        assert_eq!((&run.linked[0].from, &run.linked[0].to), (&a, &b));
+    }
+
+    #[test]
+    fn a_leftover_temporary_link_skips_that_copy() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (a, b, groups) = copies(tmp.path());
+        fs::write(link_tmp(&b), "interrupted").unwrap();
+
+        let mut run = journal::Run::new("dupes");
+        let skipped = hardlink_dupes(&groups, Keep::Oldest, &mut run).unwrap();
+        assert_eq!(skipped.len(), 1);
+        assert!(skipped[0].contains("already exists"));
+
+        assert!(run.is_empty());
+        assert_eq!(fs::read_to_string(link_tmp(&b)).unwrap(), "interrupted");
+        assert_ne!(
+            fs::metadata(&a).unwrap().ino(),
+            fs::metadata(&b).unwrap().ino()
+        );
+    }
+}
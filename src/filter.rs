@@ -0,0 +1,265 @@
+use crate::config::expand_tilde;
+use anyhow::{Context, Result, bail};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::Match;
+use ignore::gitignore::{Gitignore, Glob};
+use std::path::{Path, PathBuf};
+
+// What a command may touch beyond the built-in rules: `--include` and
+// `--exclude` globs from the command line, and `.sweeperignore` files in
+// gitignore syntax. A `.sweeperignore` in the root or a folder below it
+// excludes projects (or, for organize, files) under that folder; one inside a
+// project keeps the paths it matches out of the activity walk, like
+// `--ignore-dir`.
+
+pub const IGNORE_FILE: &str = ".sweeperignore";
+
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    include: Patterns,
+    exclude: Patterns,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Patterns {
+    globs: Vec<String>,
+    set: GlobSet,
+}
+
+impl Patterns {
+    fn new(globs: &[String]) -> Result<Self> {
+        let mut builder = GlobSetBuilder::new();
+        for glob in globs {
+            // `*` stays within one path component, as in the shell.
+            let pattern = expand_tilde(glob).to_string_lossy().to_string();
+            builder.add(
+                GlobBuilder::new(&pattern)
+                    .literal_separator(true)
+                    .build()
+                    .with_context(|| format!("Invalid glob: {}", glob))?,
+            );
+        }
+        Ok(Patterns {
+            globs: globs.to_vec(),
+            set: builder.build()?,
+        })
+    }
+
+    // The first glob matching `path` as given, relative to `root`, or by name.
+    fn first_match(&self, root: &Path, path: &Path) -> Option<&str> {
+        let rel = path.strip_prefix(root).unwrap_or(path);
+        let name = path.file_name().map(Path::new).unwrap_or(path);
+        [path, rel, name]
+            .iter()
+            .filter_map(|p| self.set.matches(p).into_iter().min())
+            .min()
+            .map(|i| self.globs[i].as_str())
+    }
+}
+
+// Something a command left alone, and the rule that made it.
+#[derive(Debug, Clone)]
+pub struct Excluded {
+    pub path: PathBuf,
+    pub rule: String,
+}
+
+impl Filter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        Ok(Filter {
+            include: Patterns::new(include)?,
+            exclude: Patterns::new(exclude)?,
+        })
+    }
+
+    // Why `path`, somewhere under `root`, is excluded by `--exclude` or by
+    // `ignores`: the `.sweeperignore` matchers of the folders above it,
+    // outermost first. The deepest file with an opinion wins, as in git.
+    pub fn excluded(
+        &self,
+        root: &Path,
+        path: &Path,
+        is_dir: bool,
+        ignores: &[Gitignore],
+    ) -> Option<String> {
+        if let Some(glob) = self.exclude.first_match(root, path) {
+            return Some(format!("--exclude {}", glob));
+        }
+        for ignore in ignores.iter().rev() {
+            match ignore.matched(path, is_dir) {
+                Match::Ignore(glob) => return Some(describe(glob)),
+                Match::Whitelist(_) => return None,
+                Match::None => {}
+            }
+        }
+        None
+    }
+
+    // Why `path` fails `--include`, if it does (nothing does when none was given).
+    pub fn not_included(&self, root: &Path, path: &Path) -> Option<String> {
+        let included =
+            self.include.globs.is_empty() || self.include.first_match(root, path).is_some();
+        (!included).then(|| "not matched by --include".to_string())
+    }
+
+    // Both of the above, for things that aren't searched any further down.
+    pub fn check(
+        &self,
+        root: &Path,
+        path: &Path,
+        is_dir: bool,
+        ignores: &[Gitignore],
+    ) -> Option<String> {
+        self.excluded(root, path, is_dir, ignores)
+            .or_else(|| self.not_included(root, path))
+    }
+}
+
+fn describe(glob: &Glob) -> String {
+    match glob.from() {
+        Some(file) => format!("{}: {}", file.display(), glob.original()),
+        None => glob.original().to_string(),
+    }
+}
+
+// The `.sweeperignore` directly inside `dir`, if there is one.
+pub fn load_ignore(dir: &Path) -> Result<Option<Gitignore>> {
+    let path = dir.join(IGNORE_FILE);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let (ignore, err) = Gitignore::new(&path);
+    if let Some(err) = err {
+        bail!("Invalid {}: {}", path.display(), err);
+    }
+    Ok(Some(ignore))
+}
+
+// `load_ignore` for commands that go on past a broken file. It is named on
+// stderr, and the error comes back as the rule that leaves its folder alone:
+// without the file, things it was meant to protect could be touched.
+pub fn load_ignore_or_skip(dir: &Path) -> std::result::Result<Option<Gitignore>, String> {
+    load_ignore(dir).map_err(|e| {
+        let rule = format!("{:#}", e);
+        eprintln!("Warning: leaving '{}' alone: {}", dir.display(), rule);
+        rule
+    })
+}
+
+pub fn print_excluded(excluded: &[Excluded]) {
+    if excluded.is_empty() {
+        return;
+    }
+    println!("Excluded:");
+    for e in excluded {
+        println!("  - {}  ({})", e.path.display(), e.rule);
+    }
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn globs(globs: &[&str]) -> Vec<String> {
+        globs.iter().map(|g| g.to_string()).collect()
+    }
+
+    fn ignore_in(dir: &Path, lines: &str) -> Gitignore {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join(IGNORE_FILE), lines).unwrap();
+        load_ignore(dir).unwrap().unwrap()
+    }
+
+    #[test]
+    fn exclude_wins_over_an_ignore_whitelist() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        let ignores = [ignore_in(root, "*.log\n!keep.log\n")];
+        let filter = Filter::new(&[], &globs(&["keep.*"])).unwrap();
+
+        let rule = filter.excluded(root, &root.join("keep.log"), false, &ignores);
+        assert_eq!(rule.as_deref(), Some("--exclude keep.*"));
+        assert!(
+            Filter::default()
+                .excluded(root, &root.join("keep.log"), false, &ignores)
+                .is_none()
+        );
+        assert!(
+            Filter::default()
+                .excluded(root, &root.join("other.log"), false, &ignores)
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn deeper_ignore_file_overrides_an_outer_one() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        let sub = root.join("sub");
+        let ignores = [ignore_in(root, "*.iso\n"), ignore_in(&sub, "!*.iso\n")];
+        let filter = Filter::default();
+
+        assert!(
+            filter
+                .excluded(root, &sub.join("disk.iso"), false, &ignores)
+                .is_none()
+        );
+        // The outer file still applies where the deeper one isn't loaded.
+        assert!(
+            filter
+                .excluded(root, &sub.join("disk.iso"), false, &ignores[..1])
+                .is_some()
+        );
+        assert!(
+            filter
+                .excluded(root, &root.join("disk.iso"), false, &ignores[..1])
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn include_only_applies_when_given() {
+        let root = Path::new("/data");
+        let path = root.join("photos/cat.jpg");
+
+        assert!(Filter::default().check(root, &path, false, &[]).is_none());
+
+        let jpgs = Filter::new(&globs(&["*.jpg"]), &[]).unwrap();
+        assert!(jpgs.check(root, &path, false, &[]).is_none());
+        let pngs = Filter::new(&globs(&["*.png"]), &[]).unwrap();
+        assert_eq!(
+            pngs.check(root, &path, false, &[]).as_deref(),
+            Some("not matched by --include")
+        );
+
+        // `*` doesn't cross folders, but a path relative to the root matches.
+        let nested = Filter::new(&globs(&["photos/*"]), &[]).unwrap();
+        assert!(nested.check(root, &path, false, &[]).is_none());
+        let flat = Filter::new(&globs(&["*/cat.jpg", "/data/*.jpg"]), &[]).unwrap();
+        assert!(
+            flat.check(root, &root.join("a/b/cat.jpg"), false, &[])
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn exclude_is_reported_before_include() {
+        let root = Path::new("/data");
+        let filter = Filter::new(&globs(&["*.jpg"]), &globs(&["cat.*"])).unwrap();
+
+        assert_eq!(
+            filter
+                .check(root, &root.join("cat.png"), false, &[])
+                .as_deref(),
+            Some("--exclude cat.*")
+        );
+        assert_eq!(
+            filter
+                .check(root, &root.join("dog.png"), false, &[])
+                .as_deref(),
+            Some("not matched by --include")
+        );
+    }
+}
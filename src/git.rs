@@ -0,0 +1,114 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+
+// Thin wrappers around the `git` binary. Everything here is best-effort: no git
+// installed, or a broken repo, simply yields `None`.
+
+pub fn is_repo(dir: &Path) -> bool {
+    // `.git` is a directory in normal checkouts and a file in worktrees/submodules.
+    dir.join(".git").exists()
+}
+
+pub(crate) fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+fn from_unix(secs: &str) -> Option<SystemTime> {
+    let secs: u64 = secs.trim().parse().ok()?;
+    Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+pub fn last_commit_time(dir: &Path) -> Option<SystemTime> {
+    from_unix(&git(dir, &["log", "-1", "--format=%ct"])?)
+}
+
+pub fn last_reflog_time(dir: &Path) -> Option<SystemTime> {
+    // `%gd` with a unix date prints e.g. `HEAD@{1760659200}`.
+    let out = git(dir, &["reflog", "-1", "--date=unix", "--format=%gd"])?;
+    let secs = out.split_once('{')?.1.strip_suffix('}')?;
+    from_unix(secs)
+}
+
+pub fn head_changed_time(dir: &Path) -> Option<SystemTime> {
+    let git_dir = PathBuf::from(git(dir, &["rev-parse", "--absolute-git-dir"])?);
+    std::fs::metadata(git_dir.join("HEAD"))
+        .and_then(|m| m.modified())
+        .ok()
+}
+
+// Whether git tracks anything under `path` (inside the checkout at `dir`).
+pub fn tracks_files_in(dir: &Path, path: &Path) -> bool {
+    git(dir, &["ls-files", "--", &path.to_string_lossy()]).is_some_and(|out| !out.is_empty())
+}
+
+// Reasons a checkout still holds work that exists nowhere else. Empty means it is
+// safe to archive or delete as far as git is concerned.
+pub fn unsaved_work(dir: &Path) -> Vec<String> {
+    let mut reasons = Vec::new();
+
+    // Without the optional lock, status doesn't refresh and rewrite `.git/index`.
+    let Some(status) = git(dir, &["--no-optional-locks", "status", "--porcelain"]) else {
+        reasons.push("could not inspect git state (is git installed?)".to_string());
+        return reasons;
+    };
+    let changes = status.lines().filter(|l| !l.is_empty()).count();
+    if changes > 0 {
+        reasons.push(format!(
+            "{} uncommitted or untracked change(s) in the working tree",
+            changes
+        ));
+    }
+
+    let stashes = git(dir, &["stash", "list"])
+        .map(|s| s.lines().filter(|l| !l.is_empty()).count())
+        .unwrap_or(0);
+    if stashes > 0 {
+        reasons.push(format!("{} stash(es)", stashes));
+    }
+
+    let refs = git(
+        dir,
+        &[
+            "for-each-ref",
+            "--format=%(refname:short)%00%(upstream:short)%00%(upstream:track)",
+            "refs/heads",
+        ],
+    )
+    .unwrap_or_default();
+    for line in refs.lines() {
+        let mut parts = line.split('\0');
+        let branch = parts.next().unwrap_or_default();
+        let upstream = parts.next().unwrap_or_default();
+        let track = parts.next().unwrap_or_default();
+
+        if upstream.is_empty() {
+            reasons.push(format!("local-only branch '{}' (no upstream)", branch));
+        } else if track.contains("gone") {
+            reasons.push(format!(
+                "branch '{}' tracks '{}', which no longer exists",
+                branch, upstream
+            ));
+        } else if let Some(ahead) = track
+            .split("ahead ")
+            .nth(1)
+            .and_then(|s| s.split([',', ']']).next())
+        {
+            reasons.push(format!(
+                "branch '{}' has {} unpushed commit(s) (ahead of '{}')",
+                branch, ahead, upstream
+            ));
+        }
+    }
+
+    reasons
+}
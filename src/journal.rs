@@ -0,0 +1,620 @@
+use crate::checksum;
+use crate::compress::{self, Compression};
+use crate::mover;
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+// Append-only log of everything the mutating commands did, one JSON line per run.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalMove {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Run {
+    pub id: String,
+    pub command: String,
+    pub started_at: DateTime<Local>,
+    // Set on runs made by `sweeper undo`: the id of the run they reverted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub undoes: Option<String>,
+    #[serde(default)]
+    pub moves: Vec<JournalMove>,
+    #[serde(default)]
+    pub trashed: Vec<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub restored: Vec<PathBuf>,
+    // Folders packed into tarballs (`from` folder, `to` tarball) and tarballs
+    // unpacked into folders (`from` tarball, `to` folder).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub packed: Vec<JournalMove>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unpacked: Vec<JournalMove>,
+    // Copies replaced by hardlinks (`from` the kept file, `to` the link). The
+    // copy itself is in `trashed`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub linked: Vec<JournalMove>,
+}
+
+impl Run {
+    pub fn new(command: &str) -> Self {
+        let started_at = Local::now();
+        Run {
+            id: started_at.format("%Y%m%d-%H%M%S-%3f").to_string(),
+            command: command.to_string(),
+            started_at,
+            undoes: None,
+            moves: Vec::new(),
+            trashed: Vec::new(),
+            restored: Vec::new(),
+            packed: Vec::new(),
+            unpacked: Vec::new(),
+            linked: Vec::new(),
+        }
+    }
+
+    // Paths are stored absolute so `undo` works from any working directory.
+    pub fn record_move(&mut self, from: &Path, to: &Path) {
+        self.moves.push(JournalMove {
+            from: absolute(from),
+            to: absolute(to),
+        });
+    }
+
+    pub fn record_trash(&mut self, path: &Path) {
+        self.trashed.push(absolute(path));
+    }
+
+    pub fn record_pack(&mut self, dir: &Path, tarball: &Path) {
+        self.packed.push(JournalMove {
+            from: absolute(dir),
+            to: absolute(tarball),
+        });
+    }
+
+    pub fn record_unpack(&mut self, tarball: &Path, dir: &Path) {
+        self.unpacked.push(JournalMove {
+            from: absolute(tarball),
+            to: absolute(dir),
+        });
+    }
+
+    pub fn record_link(&mut self, kept: &Path, link: &Path) {
+        self.linked.push(JournalMove {
+            from: absolute(kept),
+            to: absolute(link),
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+            && self.trashed.is_empty()
+            && self.restored.is_empty()
+            && self.packed.is_empty()
+            && self.unpacked.is_empty()
+            && self.linked.is_empty()
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[derive(Debug, Clone)]
+pub struct UndoReport {
+    pub run: Run,
+    pub undone_run_id: String,
+    pub conflicts: Vec<String>,
+}
+
+pub fn journal_path() -> Result<PathBuf> {
+    let state_dir = std::env::var_os("XDG_STATE_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(dirs::state_dir)
+        .or_else(dirs::data_local_dir)
+        .context("Cannot determine a state directory for the journal")?;
+    Ok(state_dir.join("sweeper").join("journal"))
+}
+
+// Run `f` with a fresh journal entry and append whatever it recorded, even when
+// `f` fails halfway: the moves that did happen still need to be undoable.
+pub fn record<F>(command: &str, f: F) -> Result<Run>
+where
+    F: FnOnce(&mut Run) -> Result<()>,
+{
+    let mut run = Run::new(command);
+    let result = f(&mut run);
+    let saved = append(&run);
+    result?;
+    saved?;
+    Ok(run)
+}
+
+pub fn append(run: &Run) -> Result<()> {
+    if run.is_empty() {
+        return Ok(());
+    }
+    append_to(&journal_path()?, run)
+}
+
+fn append_to(path: &Path, run: &Run) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create dir: {}", parent.display()))?;
+    }
+
+    let mut line = serde_json::to_string(run)?;
+    line.push('\n');
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open journal: {}", path.display()))?;
+    file.write_all(line.as_bytes())
+        .with_context(|| format!("Failed to write journal: {}", path.display()))?;
+    Ok(())
+}
+
+pub fn load() -> Result<Vec<Run>> {
+    load_from(&journal_path()?)
+}
+
+fn load_from(path: &Path) -> Result<Vec<Run>> {
+    let file = match fs::File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to open journal: {}", path.display()));
+        }
+    };
+
+    let mut runs = Vec::new();
+    for (idx, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let run: Run = serde_json::from_str(&line)
+            .with_context(|| format!("Corrupt journal entry at {}:{}", path.display(), idx + 1))?;
+        runs.push(run);
+    }
+    Ok(runs)
+}
+
+// Reverse a run: moves are put back in reverse order and trashed paths are
+// restored from the system bin. Anything that would overwrite a path recreated
+// since the run is left alone and reported as a conflict; the moves themselves
+// never replace an existing path either, even one created while undo runs.
+// Undoing the same run again later picks up whatever was left.
+pub fn undo(run_id: Option<&str>) -> Result<UndoReport> {
+    undo_in(&journal_path()?, run_id)
+}
+
+fn undo_in(journal: &Path, run_id: Option<&str>) -> Result<UndoReport> {
+    let runs = load_from(journal)?;
+
+    let target = match run_id {
+        Some(id) => runs
+            .iter()
+            .find(|r| r.id == id)
+            .with_context(|| format!("No run with id '{}' in the journal", id))?,
+        None => runs
+            .iter()
+            .rev()
+            .find(|r| {
+                r.undoes.is_none() && !runs.iter().any(|u| u.undoes.as_deref() == Some(&r.id))
+            })
+            .context("Nothing to undo")?,
+    };
+
+    if target.undoes.is_some() {
+        bail!(
+            "Run '{}' is itself an undo and cannot be reversed",
+            target.id
+        );
+    }
+
+    let mut conflicts = Vec::new();
+    let mut run = Run::new("undo");
+    run.undoes = Some(target.id.clone());
+
+    for mv in target.moves.iter().rev() {
+        let original_taken = fs::symlink_metadata(&mv.from).is_ok();
+        let moved_present = fs::symlink_metadata(&mv.to).is_ok();
+
+        match (original_taken, moved_present) {
+            // Already put back by an earlier (partial) undo.
+            (true, false) => continue,
+            (true, true) => {
+                conflicts.push(format!(
+                    "'{}' has been recreated; leaving '{}' where it is",
+                    mv.from.display(),
+                    mv.to.display()
+                ));
+                continue;
+            }
+            (false, false) => {
+                conflicts.push(format!(
+                    "'{}' no longer exists; cannot restore '{}'",
+                    mv.to.display(),
+                    mv.from.display()
+                ));
+                continue;
+            }
+            (false, true) => {}
+        }
+
+        if let Some(parent) = mv.from.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create dir: {}", parent.display()))?;
+        }
+        if let Err(e) = mover::move_path(&mv.to, &mv.from, None) {
+            conflicts.push(format!("{:#}", e));
+            continue;
+        }
+        run.record_move(&mv.to, &mv.from);
+        forget_archived(target, &mv.to, &mut conflicts);
+    }
+
+    // A packed folder comes back by unpacking its tarball, an unpacked one goes
+    // back into a tarball of the same kind.
+    for mv in target.unpacked.iter().rev() {
+        let Some(compression) = Compression::from_path(&mv.from) else {
+            continue;
+        };
+        if reverse_precheck(&mv.from, &mv.to, &mut conflicts) {
+            match compress::pack(&mv.to, &mv.from, compression, None) {
+                Ok(()) => run.record_pack(&mv.to, &mv.from),
+                Err(e) => conflicts.push(format!("{:#}", e)),
+            }
+        }
+    }
+    for mv in target.packed.iter().rev() {
+        let Some(compression) = Compression::from_path(&mv.to) else {
+            continue;
+        };
+        if reverse_precheck(&mv.from, &mv.to, &mut conflicts) {
+            match compress::unpack(&mv.to, &mv.from, compression) {
+                Ok(()) => {
+                    run.record_unpack(&mv.to, &mv.from);
+                    forget_archived(target, &mv.to, &mut conflicts);
+                }
+                Err(e) => conflicts.push(format!("{:#}", e)),
+            }
+        }
+    }
+
+    // A copy replaced by a hardlink comes back from the bin once the link is
+    // out of the way. A link that no longer points at the kept file has been
+    // replaced since and is left for `restore_trashed` to report.
+    let mut unlinked = Vec::new();
+    for link in target.linked.iter().rev() {
+        if !same_file(&link.from, &link.to) {
+            continue;
+        }
+        match fs::remove_file(&link.to) {
+            Ok(()) => unlinked.push(link),
+            Err(e) => conflicts.push(format!(
+                "Failed to remove link '{}': {}",
+                link.to.display(),
+                e
+            )),
+        }
+    }
+
+    // Whatever an earlier undo of this run already got back from the bin.
+    let mut remaining = target.clone();
+    remaining.trashed.retain(|path| {
+        !runs
+            .iter()
+            .any(|u| u.undoes.as_deref() == Some(&target.id) && u.restored.contains(path))
+    });
+    restore_trashed(&remaining, &mut run, &mut conflicts)?;
+
+    // A copy that couldn't be restored gets its link back, so the name isn't lost.
+    for link in unlinked {
+        if !run.restored.contains(&link.to)
+            && let Err(e) = fs::hard_link(&link.from, &link.to)
+        {
+            conflicts.push(format!(
+                "Failed to relink '{}' -> '{}': {}",
+                link.to.display(),
+                link.from.display(),
+                e
+            ));
+        }
+    }
+
+    if !run.is_empty() {
+        append_to(journal, &run)?;
+    }
+
+    Ok(UndoReport {
+        run,
+        undone_run_id: target.id.clone(),
+        conflicts,
+    })
+}
+
+// A project taken back out of the archive leaves its checksums behind, so
+// `verify` doesn't report it as lost.
+fn forget_archived(target: &Run, archived: &Path, conflicts: &mut Vec<String>) {
+    if target.command == "archive"
+        && let Err(e) = checksum::remove(archived)
+    {
+        conflicts.push(format!("{:#}", e));
+    }
+}
+
+#[cfg(unix)]
+fn same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (fs::symlink_metadata(a), fs::symlink_metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_file(_a: &Path, _b: &Path) -> bool {
+    false
+}
+
+// Whether `current` can be turned back into `original`; same rules as for moves.
+fn reverse_precheck(original: &Path, current: &Path, conflicts: &mut Vec<String>) -> bool {
+    let original_taken = fs::symlink_metadata(original).is_ok();
+    let current_present = fs::symlink_metadata(current).is_ok();
+    match (original_taken, current_present) {
+        (false, true) => true,
+        (true, false) => false,
+        (true, true) => {
+            conflicts.push(format!(
+                "'{}' has been recreated; leaving '{}' where it is",
+                original.display(),
+                current.display()
+            ));
+            false
+        }
+        (false, false) => {
+            conflicts.push(format!(
+                "'{}' no longer exists; cannot restore '{}'",
+                current.display(),
+                original.display()
+            ));
+            false
+        }
+    }
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+))]
+fn restore_trashed(target: &Run, run: &mut Run, conflicts: &mut Vec<String>) -> Result<()> {
+    if target.trashed.is_empty() {
+        return Ok(());
+    }
+
+    let mut items = trash::os_limited::list().context("Failed to list the system bin")?;
+    // Earliest deletion first, so we pick the copy trashed by this run and not a
+    // later one with the same original path.
+    items.sort_by_key(|i| i.time_deleted);
+    let since = target.started_at.timestamp() - 1;
+
+    for path in &target.trashed {
+        if fs::symlink_metadata(path).is_ok() {
+            conflicts.push(format!(
+                "'{}' has been recreated; leaving the trashed copy in the bin",
+                path.display()
+            ));
+            continue;
+        }
+
+        let Some(pos) = items
+            .iter()
+            .position(|i| i.time_deleted >= since && &i.original_path() == path)
+        else {
+            conflicts.push(format!(
+                "'{}' is no longer in the system bin",
+                path.display()
+            ));
+            continue;
+        };
+
+        let item = items.remove(pos);
+        if let Err(e) = trash::os_limited::restore_all([item]) {
+            conflicts.push(format!("Failed to restore '{}': {}", path.display(), e));
+            continue;
+        }
+        run.restored.push(path.clone());
+    }
+
+    Ok(())
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+)))]
+fn restore_trashed(target: &Run, _run: &mut Run, conflicts: &mut Vec<String>) -> Result<()> {
+    for path in &target.trashed {
+        conflicts.push(format!(
+            "Restoring from the system bin is not supported on this platform: '{}'",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Move `from` to `to` the way a command would, and journal it.
+    fn moved(journal: &Path, from: &Path, to: &Path) -> Run {
+        let mut run = Run::new("archive");
+        fs::create_dir_all(to.parent().unwrap()).unwrap();
+        mover::move_path(from, to, None).unwrap();
+        run.record_move(from, to);
+        append_to(journal, &run).unwrap();
+        run
+    }
+
+    #[test]
+    fn undo_puts_moves_back_once() {
+        let tmp = tempfile::tempdir().unwrap();
+        let journal = tmp.path().join("journal");
+        let from = tmp.path().join("code/app");
+        let to = tmp.path().join("archive/app");
+        fs::create_dir_all(&from).unwrap();
+        fs::write(from.join("main.rs"), "fn main() {}\n").unwrap();
+        let run = moved(&journal, &from, &to);
+
+        let report = undo_in(&journal, None).unwrap();
+        assert_eq!(report.undone_run_id, run.id);
+        assert!(report.conflicts.is_empty());
+        assert_eq!(report.run.moves.len(), 1);
+        assert!(!to.exists());
+        assert_eq!(
+            fs::read_to_string(from.join("main.rs")).unwrap(),
+            "fn main() {}\n"
+        );
+
+        let runs = load_from(&journal).unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[1].undoes.as_deref(), Some(run.id.as_str()));
+        assert!(undo_in(&journal, None).is_err());
+    }
+
+    #[test]
+    fn undoing_an_archive_forgets_its_checksums() {
+        let tmp = tempfile::tempdir().unwrap();
+        let journal = tmp.path().join("journal");
+        let from = tmp.path().join("code/app");
+        let to = tmp.path().join("archive/2026-01/app");
+        fs::create_dir_all(&from).unwrap();
+        fs::write(from.join("main.rs"), "fn main() {}\n").unwrap();
+        let sums = checksum::hash_tree(&from, checksum::Algorithm::Blake3).unwrap();
+        moved(&journal, &from, &to);
+        checksum::save(&to, &sums).unwrap();
+
+        let report = undo_in(&journal, None).unwrap();
+        assert!(report.conflicts.is_empty());
+        assert!(from.is_dir());
+        assert!(checksum::load(&to).unwrap().is_none());
+    }
+
+    #[test]
+    fn undo_replaces_a_hardlink_with_the_trashed_copy() {
+        let tmp = tempfile::tempdir().unwrap();
+        let journal = tmp.path().join("journal");
+        let dir = tmp.path().join("photos");
+        fs::create_dir_all(&dir).unwrap();
+        let (kept, copy) = (dir.join("a.jpg"), dir.join("b.jpg"));
+        fs::write(&kept, "pixels").unwrap();
+        fs::write(&copy, "pixels").unwrap();
+
+        let groups = crate::dupes::find_dupes(&dir).unwrap().groups;
+        let mut run = Run::new("dupes");
+        crate::dupes::hardlink_dupes(&groups, crate::dupes::Keep::Oldest, &mut run).unwrap();
+        append_to(&journal, &run).unwrap();
+        let (kept, copy) = (&run.linked[0].from, &run.linked[0].to);
+        assert!(same_file(kept, copy));
+
+        let report = undo_in(&journal, None).unwrap();
+        assert!(report.conflicts.is_empty(), "{:?}", report.conflicts);
+        assert_eq!(report.run.restored, [copy.as_path()]);
+        assert!(!same_file(kept, copy));
+        assert_eq!(fs::read_to_string(copy).unwrap(), "pixels");
+    }
+
+    #[test]
+    fn undoing_a_restore_brings_the_checksums_back() {
+        let tmp = tempfile::tempdir().unwrap();
+        let journal = tmp.path().join("journal");
+        let archived = tmp.path().join("archive/2026-01/app");
+        fs::create_dir_all(&archived).unwrap();
+        fs::write(archived.join("main.rs"), "fn main() {}\n").unwrap();
+        let sums = checksum::hash_tree(&archived, checksum::Algorithm::Blake3).unwrap();
+        checksum::save(&archived, &sums).unwrap();
+
+        let plan = crate::restore::RestorePlan {
+            from: archived.clone(),
+            to: tmp.path().join("code/app"),
+            note: None,
+        };
+        let mut run = Run::new("restore");
+        crate::restore::apply_restore(&plan, &mut run).unwrap();
+        append_to(&journal, &run).unwrap();
+        assert!(checksum::load(&archived).unwrap().is_none());
+
+        let report = undo_in(&journal, None).unwrap();
+        assert!(report.conflicts.is_empty(), "{:?}", report.conflicts);
+        assert!(archived.join("main.rs").is_file());
+        let back = checksum::load(&archived).unwrap().unwrap();
+        assert_eq!(back.files, sums.files);
+    }
+
+    #[test]
+    fn undo_leaves_recreated_paths_alone_and_can_be_retried() {
+        let tmp = tempfile::tempdir().unwrap();
+        let journal = tmp.path().join("journal");
+        let from = tmp.path().join("Downloads/report.pdf");
+        let to = tmp.path().join("Downloads/Documents/report.pdf");
+        fs::create_dir_all(from.parent().unwrap()).unwrap();
+        fs::write(&from, "old").unwrap();
+        let run = moved(&journal, &from, &to);
+        fs::write(&from, "new").unwrap();
+
+        let report = undo_in(&journal, None).unwrap();
+        assert_eq!(report.conflicts.len(), 1);
+        assert!(report.run.is_empty());
+        assert_eq!(fs::read_to_string(&from).unwrap(), "new");
+        assert_eq!(fs::read_to_string(&to).unwrap(), "old");
+
+        fs::remove_file(&from).unwrap();
+        let report = undo_in(&journal, Some(&run.id)).unwrap();
+        assert!(report.conflicts.is_empty());
+        assert_eq!(fs::read_to_string(&from).unwrap(), "old");
+        assert!(!to.exists());
+    }
+
+    #[test]
+    fn undo_never_moves_onto_an_existing_path() {
+        let tmp = tempfile::tempdir().unwrap();
+        let from = tmp.path().join("app");
+        let to = tmp.path().join("empty");
+        fs::create_dir_all(&from).unwrap();
+        fs::create_dir_all(&to).unwrap();
+
+        // What undo does after its check, if the path appeared in between.
+        assert!(mover::move_path(&from, &to, None).is_err());
+        assert!(from.is_dir());
+        assert!(to.is_dir());
+
+        let file = tmp.path().join("file");
+        fs::write(&file, "keep").unwrap();
+        fs::write(from.join("a"), "a").unwrap();
+        assert!(mover::move_path(&from.join("a"), &file, None).is_err());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "keep");
+    }
+}
@@ -0,0 +1,417 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+// What kind of project a folder is, going by the marker files at its top, and
+// which of its folders are build output that the ecosystem's tools recreate.
+
+// The one kind a project is reported and filtered under. Marker files are
+// checked in this order, so a Rust crate with a `package.json` is Rust; file
+// extensions at the top level only count when no marker matched.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    clap::ValueEnum,
+    Serialize,
+    Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectKind {
+    Rust,
+    Go,
+    Node,
+    Python,
+    Java,
+    #[value(alias = "c")]
+    Cpp,
+    Latex,
+    Jupyter,
+    /// Anything without a recognized marker
+    Plain,
+}
+
+// Marker files by the kind of project they make a folder, in detection order,
+// and the ecosystem whose build output `sweeper clean` then looks for. Project
+// detection and cleaning both go by this one table, so they can't disagree
+// about what a folder is.
+const MARKERS: &[(ProjectKind, Option<Ecosystem>, &[&str])] = &[
+    (ProjectKind::Rust, Some(Ecosystem::Rust), &["Cargo.toml"]),
+    (ProjectKind::Go, None, &["go.mod"]),
+    (ProjectKind::Node, Some(Ecosystem::Node), &["package.json"]),
+    (
+        ProjectKind::Python,
+        Some(Ecosystem::Python),
+        &[
+            "pyproject.toml",
+            "setup.py",
+            "setup.cfg",
+            "requirements.txt",
+            "Pipfile",
+        ],
+    ),
+    (ProjectKind::Java, Some(Ecosystem::Maven), &["pom.xml"]),
+    (
+        ProjectKind::Java,
+        Some(Ecosystem::Gradle),
+        &["build.gradle", "build.gradle.kts", "settings.gradle"],
+    ),
+    (
+        ProjectKind::Cpp,
+        Some(Ecosystem::Cmake),
+        &["CMakeLists.txt"],
+    ),
+    (ProjectKind::Cpp, None, &["meson.build", "configure.ac"]),
+];
+
+const KIND_EXTENSIONS: &[(ProjectKind, &[&str])] = &[
+    (ProjectKind::Cpp, &["c", "cc", "cpp", "cxx", "h", "hpp"]),
+    (ProjectKind::Latex, &["tex"]),
+    (ProjectKind::Jupyter, &["ipynb"]),
+    (ProjectKind::Python, &["py"]),
+];
+
+impl ProjectKind {
+    pub fn detect(dir: &Path) -> Self {
+        let names: Vec<String> = fs::read_dir(dir)
+            .map(|rd| {
+                rd.filter_map(|e| e.ok())
+                    .filter(|e| e.file_type().is_ok_and(|t| t.is_file()))
+                    .map(|e| e.file_name().to_string_lossy().to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let has = |marker: &str| names.iter().any(|n| n == marker);
+        if let Some((kind, _, _)) = MARKERS
+            .iter()
+            .find(|(_, _, markers)| markers.iter().any(|m| has(m)))
+        {
+            return *kind;
+        }
+
+        let ext_of = |n: &String| {
+            Path::new(n)
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+        };
+        KIND_EXTENSIONS
+            .iter()
+            .find(|(_, exts)| {
+                names
+                    .iter()
+                    .filter_map(ext_of)
+                    .any(|e| exts.contains(&e.as_str()))
+            })
+            .map(|(kind, _)| *kind)
+            .unwrap_or(ProjectKind::Plain)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ProjectKind::Rust => "Rust",
+            ProjectKind::Go => "Go",
+            ProjectKind::Node => "Node",
+            ProjectKind::Python => "Python",
+            ProjectKind::Java => "Java",
+            ProjectKind::Cpp => "C/C++",
+            ProjectKind::Latex => "LaTeX",
+            ProjectKind::Jupyter => "Jupyter",
+            ProjectKind::Plain => "Plain folder",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Vcs {
+    Git,
+    Hg,
+    Svn,
+}
+
+impl Vcs {
+    // The checkout `dir` is the root of, if any. (`.git` is a file in worktrees
+    // and submodules, so existence is what counts.)
+    pub fn detect(dir: &Path) -> Option<Self> {
+        [(Vcs::Git, ".git"), (Vcs::Hg, ".hg"), (Vcs::Svn, ".svn")]
+            .into_iter()
+            .find(|(_, marker)| dir.join(marker).exists())
+            .map(|(vcs, _)| vcs)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Vcs::Git => "git",
+            Vcs::Hg => "hg",
+            Vcs::Svn => "svn",
+        }
+    }
+}
+
+// A file that marks its folder as a project when nothing else does.
+pub const PROJECT_MARKER: &str = ".sweeper-project";
+
+// Whether `dir` is a project in its own right: a checkout, something with a
+// build manifest, or a folder marked with `.sweeper-project`. Discovery stops
+// descending there.
+pub fn is_project_root(dir: &Path) -> bool {
+    Vcs::detect(dir).is_some()
+        || dir.join(PROJECT_MARKER).is_file()
+        || MARKERS
+            .iter()
+            .flat_map(|(_, _, markers)| markers.iter())
+            .any(|m| dir.join(m).is_file())
+}
+
+// Ecosystems with build output `sweeper clean` knows how to remove.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Ecosystem {
+    Rust,
+    Node,
+    Python,
+    Maven,
+    Gradle,
+    Cmake,
+}
+
+impl Ecosystem {
+    pub const ALL: [Ecosystem; 6] = [
+        Ecosystem::Rust,
+        Ecosystem::Node,
+        Ecosystem::Python,
+        Ecosystem::Maven,
+        Ecosystem::Gradle,
+        Ecosystem::Cmake,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Ecosystem::Rust => "rust",
+            Ecosystem::Node => "node",
+            Ecosystem::Python => "python",
+            Ecosystem::Maven => "maven",
+            Ecosystem::Gradle => "gradle",
+            Ecosystem::Cmake => "cmake",
+        }
+    }
+
+    fn markers(self) -> impl Iterator<Item = &'static str> {
+        MARKERS
+            .iter()
+            .filter(move |(_, eco, _)| *eco == Some(self))
+            .flat_map(|(_, _, markers)| markers.iter().copied())
+    }
+
+    fn artifacts(self) -> &'static [Artifact] {
+        match self {
+            Ecosystem::Rust | Ecosystem::Maven => TARGET,
+            Ecosystem::Node => NODE,
+            Ecosystem::Python => PYTHON,
+            Ecosystem::Gradle => GRADLE,
+            Ecosystem::Cmake => CMAKE,
+        }
+    }
+}
+
+const TARGET: &[Artifact] = &[Artifact::top("target")];
+const NODE: &[Artifact] = &[
+    Artifact::top("node_modules"),
+    Artifact::top(".next"),
+    Artifact::top(".nuxt"),
+    Artifact::top(".svelte-kit"),
+    Artifact::top(".parcel-cache"),
+    Artifact::top(".turbo"),
+];
+const PYTHON: &[Artifact] = &[
+    Artifact::top(".venv"),
+    Artifact::top("venv"),
+    Artifact::top(".tox"),
+    Artifact::top(".nox"),
+    Artifact::top(".pytest_cache"),
+    Artifact::top(".mypy_cache"),
+    Artifact::top(".ruff_cache"),
+    Artifact::nested("__pycache__"),
+];
+const GRADLE: &[Artifact] = &[Artifact::top("build"), Artifact::top(".gradle")];
+// Only a configured CMake tree counts, never a hand-made `build/`.
+const CMAKE: &[Artifact] = &[Artifact {
+    name: "build",
+    nested: false,
+    requires: Some("CMakeCache.txt"),
+}];
+
+impl fmt::Display for Ecosystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+struct Artifact {
+    name: &'static str,
+    // Also look for it in subfolders (e.g. every `__pycache__`), not just at the top.
+    nested: bool,
+    // A file the folder must contain to be taken for build output.
+    requires: Option<&'static str>,
+}
+
+impl Artifact {
+    const fn top(name: &'static str) -> Self {
+        Artifact {
+            name,
+            nested: false,
+            requires: None,
+        }
+    }
+
+    const fn nested(name: &'static str) -> Self {
+        Artifact {
+            name,
+            nested: true,
+            requires: None,
+        }
+    }
+
+    fn matches(&self, dir: &Path) -> bool {
+        // Symlinks are left alone: removing one wouldn't free anything here.
+        fs::symlink_metadata(dir).is_ok_and(|m| m.is_dir())
+            && self.requires.is_none_or(|f| dir.join(f).is_file())
+    }
+}
+
+// Every ecosystem whose marker files sit at the top of `dir`. A project can be
+// several at once (a Rust crate with a JS frontend, say).
+pub fn detect(dir: &Path) -> Vec<Ecosystem> {
+    Ecosystem::ALL
+        .into_iter()
+        .filter(|e| e.markers().any(|m| dir.join(m).is_file()))
+        .collect()
+}
+
+// Artifact folders of `project` for the given ecosystems, without duplicates
+// and without folders inside other ones (no `__pycache__` under `.venv`).
+pub fn artifact_dirs(project: &Path, ecosystems: &[Ecosystem]) -> Vec<(Ecosystem, PathBuf)> {
+    let mut found: Vec<(Ecosystem, PathBuf)> = Vec::new();
+
+    for &eco in ecosystems {
+        for artifact in eco.artifacts().iter().filter(|a| !a.nested) {
+            let dir = project.join(artifact.name);
+            if artifact.matches(&dir) && !found.iter().any(|(_, d)| *d == dir) {
+                found.push((eco, dir));
+            }
+        }
+    }
+
+    for &eco in ecosystems {
+        for artifact in eco.artifacts().iter().filter(|a| a.nested) {
+            let mut walk = WalkDir::new(project).follow_links(false).into_iter();
+            while let Some(entry) = walk.next() {
+                let Ok(entry) = entry else {
+                    continue;
+                };
+                if !entry.file_type().is_dir() || entry.depth() == 0 {
+                    continue;
+                }
+                let path = entry.path();
+                if entry.file_name() == ".git" || found.iter().any(|(_, d)| d == path) {
+                    walk.skip_current_dir();
+                } else if entry.file_name() == artifact.name && artifact.matches(path) {
+                    found.push((eco, path.to_path_buf()));
+                    walk.skip_current_dir();
+                }
+            }
+        }
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mkdir(path: &Path) {
+        fs::create_dir_all(path).unwrap();
+    }
+
+    fn found(project: &Path, ecosystems: &[Ecosystem]) -> Vec<(Ecosystem, String)> {
+        let mut found: Vec<_> = artifact_dirs(project, ecosystems)
+            .into_iter()
+            .map(|(e, p)| (e, p.strip_prefix(project).unwrap().display().to_string()))
+            .collect();
+        found.sort_by(|a, b| a.1.cmp(&b.1));
+        found
+    }
+
+    #[test]
+    fn artifact_dirs_are_listed_once_and_not_inside_each_other() {
+        let tmp = tempfile::tempdir().unwrap();
+        let p = tmp.path();
+        mkdir(&p.join("target/debug"));
+        mkdir(&p.join("node_modules/left-pad"));
+        mkdir(&p.join(".venv/lib/__pycache__"));
+        mkdir(&p.join("src/pkg/__pycache__"));
+        mkdir(&p.join(".git/__pycache__"));
+
+        assert_eq!(
+            found(p, &[Ecosystem::Rust, Ecosystem::Maven, Ecosystem::Python]),
+            [
+                (Ecosystem::Python, ".venv".to_string()),
+                (Ecosystem::Python, "src/pkg/__pycache__".to_string()),
+                (Ecosystem::Rust, "target".to_string()),
+            ]
+        );
+        assert!(found(p, &[]).is_empty());
+    }
+
+    #[test]
+    fn a_cmake_build_folder_needs_a_cache_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let p = tmp.path();
+        mkdir(&p.join("build"));
+        assert!(found(p, &[Ecosystem::Cmake]).is_empty());
+
+        fs::write(p.join("build/CMakeCache.txt"), "").unwrap();
+        assert_eq!(
+            found(p, &[Ecosystem::Cmake]),
+            [(Ecosystem::Cmake, "build".to_string())]
+        );
+    }
+
+    #[test]
+    fn kinds_and_ecosystems_come_from_the_same_markers() {
+        let tmp = tempfile::tempdir().unwrap();
+        let p = tmp.path();
+        fs::write(p.join("Pipfile"), "").unwrap();
+        assert!(is_project_root(p));
+        assert_eq!(ProjectKind::detect(p), ProjectKind::Python);
+        assert_eq!(detect(p), [Ecosystem::Python]);
+
+        fs::write(p.join("build.gradle"), "").unwrap();
+        fs::write(p.join("pom.xml"), "").unwrap();
+        assert_eq!(ProjectKind::detect(p), ProjectKind::Python);
+        assert_eq!(
+            detect(p),
+            [Ecosystem::Python, Ecosystem::Maven, Ecosystem::Gradle]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_artifact_dirs_are_left_alone() {
+        let tmp = tempfile::tempdir().unwrap();
+        let p = tmp.path();
+        mkdir(&p.join("elsewhere"));
+        std::os::unix::fs::symlink(p.join("elsewhere"), p.join("node_modules")).unwrap();
+        assert!(found(p, &[Ecosystem::Node]).is_empty());
+    }
+}
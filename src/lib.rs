@@ -1,39 +1,348 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
+use checksum::Algorithm;
 use chrono::{DateTime, Local};
+use compress::Compression;
+use filter::{Excluded, Filter};
+use ignore::gitignore::Gitignore;
+use kind::{ProjectKind, Vcs};
+use rules::{FileFacts, RuleSet};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::time::{Duration, SystemTime};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime};
 use walkdir::WalkDir;
 
+pub mod cache;
+pub mod checksum;
+pub mod clean;
+pub mod compress;
+pub mod config;
+pub mod dupes;
+pub mod filter;
+pub mod git;
+pub mod journal;
+pub mod kind;
+pub mod manifest;
+pub mod mover;
+pub mod output;
+pub mod restore;
+pub mod rules;
+pub mod sniff;
+pub mod tui;
+pub mod watch;
+
 #[derive(Debug, Clone)]
 pub struct ProjectItem {
     pub path: PathBuf,
+    pub kind: ProjectKind,
+    // Set when the folder is the root of a checkout.
+    pub vcs: Option<Vcs>,
     pub last_modified: SystemTime,
+    pub activity_source: ActivitySource,
+    // Only measured for stale projects, and not at all when sizing is off.
+    pub size: Option<ProjectSize>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectSize {
+    pub apparent_bytes: u64,
+    pub disk_bytes: u64,
+    pub file_count: u64,
+}
+
+impl ProjectSize {
+    fn add(&mut self, other: &ProjectSize) {
+        self.apparent_bytes += other.apparent_bytes;
+        self.disk_bytes += other.disk_bytes;
+        self.file_count += other.file_count;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    /// Oldest activity first
+    #[default]
+    Age,
+    /// Largest on-disk size first
+    Size,
+}
+
+// Which signal decides how recently a project was worked on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActivityMode {
+    /// Newest file mtime in the tree
+    #[default]
+    Mtime,
+    /// Last commit time when the folder is a repository, mtime otherwise
+    Git,
+    /// Newest of the last commit, reflog and HEAD change, mtime otherwise
+    #[serde(rename = "git-any")]
+    GitAny,
+    /// Whichever of mtime and the last commit is most recent
+    Max,
+}
+
+// The signal that produced `ProjectItem::last_modified`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActivitySource {
+    Mtime,
+    GitCommit,
+    GitReflog,
+    GitHead,
+}
+
+impl ActivitySource {
+    pub fn label(self) -> &'static str {
+        match self {
+            ActivitySource::Mtime => "mtime",
+            ActivitySource::GitCommit => "git commit",
+            ActivitySource::GitReflog => "git reflog",
+            ActivitySource::GitHead => "git HEAD",
+        }
+    }
+}
+
+// How far below a project folder the activity walk descends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Depth {
+    Limited(usize),
+    Unlimited,
+}
+
+impl FromStr for Depth {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("unlimited") {
+            return Ok(Depth::Unlimited);
+        }
+        s.parse()
+            .map(Depth::Limited)
+            .map_err(|_| format!("expected a number or 'unlimited', got '{}'", s))
+    }
+}
+
+// In config files: `depth = 5` or `depth = "unlimited"`.
+impl Serialize for Depth {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            Depth::Limited(n) => s.serialize_u64(*n as u64),
+            Depth::Unlimited => s.serialize_str("unlimited"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Depth {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Number(usize),
+            Text(String),
+        }
+        match Repr::deserialize(d)? {
+            Repr::Number(n) => Ok(Depth::Limited(n)),
+            Repr::Text(s) => s.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+impl fmt::Display for Depth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Depth::Limited(n) => write!(f, "{}", n),
+            Depth::Unlimited => write!(f, "unlimited"),
+        }
+    }
 }
 
+// Build output and dependency folders. Tools rewrite these all the time, so they
+// say nothing about whether anyone is actually working on the project.
+pub const DEFAULT_IGNORED_DIRS: &[&str] = &[
+    "target",
+    "node_modules",
+    ".venv",
+    "venv",
+    "__pycache__",
+    ".mypy_cache",
+    ".pytest_cache",
+    ".tox",
+    "build",
+    "dist",
+    ".gradle",
+    ".next",
+    ".nuxt",
+    ".parcel-cache",
+    ".turbo",
+    ".cache",
+];
+
 #[derive(Debug, Clone)]
-pub struct ScanReport {
-    pub root: PathBuf,
+pub struct ScanOptions {
     pub older_than_days: u64,
+    pub activity: ActivityMode,
+    pub depth: Depth,
+    // Stop walking a project at the first file newer than the cutoff. Such a
+    // project is fresh either way; its `last_modified` is then just "recent enough".
+    pub early_exit: bool,
+    // Directory names skipped when measuring activity. Still counted for size.
+    pub ignore_dirs: Vec<String>,
+    // Walk the whole tree of each stale project to total up sizes and file counts.
+    pub sizes: bool,
+    pub sort: SortKey,
+    // Only scan projects of these kinds; empty means all.
+    pub kinds: Vec<ProjectKind>,
+    // How many levels below the root to look for projects. At 1 every
+    // immediate subfolder is one; deeper, see `discover_projects`.
+    pub discover_depth: usize,
+    // `--include`/`--exclude`; `.sweeperignore` files are read as the scan goes.
+    pub filter: Filter,
+    // How many projects are walked at once.
+    pub jobs: usize,
+    // Reuse the scan index for folders that haven't changed.
+    pub cache: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            older_than_days: 30,
+            activity: ActivityMode::Mtime,
+            depth: Depth::Unlimited,
+            early_exit: true,
+            ignore_dirs: DEFAULT_IGNORED_DIRS.iter().map(|s| s.to_string()).collect(),
+            sizes: true,
+            sort: SortKey::Age,
+            kinds: Vec::new(),
+            discover_depth: 1,
+            filter: Filter::default(),
+            jobs: default_jobs(),
+            cache: true,
+        }
+    }
+}
+
+// One worker per core, but not so many that a spinning disk spends all its
+// time seeking.
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(8)
+}
+
+// One root of a scan, with the settings it was scanned under.
+#[derive(Debug, Clone)]
+pub struct RootScan {
+    pub path: PathBuf,
+    pub older_than_days: u64,
+    pub scanned_count: usize,
+    // Wall-clock time the scan took.
+    pub duration: Duration,
+}
+
+// Projects found under one or more roots (see `ScanReport::merge`).
+#[derive(Debug, Clone)]
+pub struct ScanReport {
+    pub roots: Vec<RootScan>,
     pub stale: Vec<ProjectItem>,
     pub fresh: Vec<ProjectItem>,
-    pub scanned_count: usize,
+    // Folders the filter kept out of the scan.
+    pub excluded: Vec<Excluded>,
+    pub sort: SortKey,
+}
+
+impl ScanReport {
+    // One report covering every root in `reports`, sorted by `sort`.
+    pub fn merge(reports: Vec<ScanReport>, sort: SortKey) -> Self {
+        let mut merged = ScanReport {
+            roots: Vec::new(),
+            stale: Vec::new(),
+            fresh: Vec::new(),
+            excluded: Vec::new(),
+            sort,
+        };
+        for report in reports {
+            merged.roots.extend(report.roots);
+            merged.stale.extend(report.stale);
+            merged.fresh.extend(report.fresh);
+            merged.excluded.extend(report.excluded);
+        }
+        merged.sort_by(sort);
+        merged
+    }
+
+    pub fn scanned_count(&self) -> usize {
+        self.roots.iter().map(|r| r.scanned_count).sum()
+    }
+
+    // Roots are scanned one after another, so this is the wall-clock total.
+    pub fn duration(&self) -> Duration {
+        self.roots.iter().map(|r| r.duration).sum()
+    }
+
+    // The threshold shared by every root, if they agree on one.
+    pub fn older_than_days(&self) -> Option<u64> {
+        let first = self.roots.first()?.older_than_days;
+        self.roots
+            .iter()
+            .all(|r| r.older_than_days == first)
+            .then_some(first)
+    }
+
+    // The root `item` was found under.
+    pub fn root_of(&self, item: &ProjectItem) -> Option<&RootScan> {
+        self.roots.iter().find(|r| item.path.starts_with(&r.path))
+    }
+
+    pub fn sort_by(&mut self, key: SortKey) {
+        for items in [&mut self.stale, &mut self.fresh] {
+            match key {
+                SortKey::Age => items.sort_by_key(|p| p.last_modified),
+                SortKey::Size => items
+                    .sort_by_key(|p| std::cmp::Reverse(p.size.map(|s| s.disk_bytes).unwrap_or(0))),
+            }
+        }
+        self.sort = key;
+    }
+
+    // What archiving or deleting every stale project would free up.
+    pub fn reclaimable(&self) -> ProjectSize {
+        let mut total = ProjectSize::default();
+        for size in self.stale.iter().filter_map(|p| p.size.as_ref()) {
+            total.add(size);
+        }
+        total
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ArchiveMove {
     pub from: PathBuf,
     pub to: PathBuf,
+    // What the scan knew about the project; goes into the bucket manifest.
+    pub item: ProjectItem,
 }
 
 #[derive(Debug, Clone)]
 pub struct ArchivePlan {
     pub dest_root: PathBuf,
     pub month_bucket: String, // e.g. "2026-02"
+    // Projects are moved as folders, or packed into `name.tar.*` files.
+    pub compression: Compression,
+    // Hash every file before archiving and check the archived copy against it.
+    pub checksum: Option<Algorithm>,
     pub moves: Vec<ArchiveMove>,
 }
 
-pub fn scan_projects(root: &Path, older_than_days: u64) -> Result<ScanReport> {
+pub fn scan_projects(root: &Path, opts: &ScanOptions) -> Result<ScanReport> {
+    let older_than_days = opts.older_than_days;
     let root = root
         .canonicalize()
         .with_context(|| format!("Cannot access path: {}", root.display()))?;
@@ -42,73 +351,394 @@ pub fn scan_projects(root: &Path, older_than_days: u64) -> Result<ScanReport> {
         .checked_sub(Duration::from_secs(older_than_days * 24 * 60 * 60))
         .context("Failed to compute cutoff time")?;
 
-    // Only scan immediate subdirectories (projects), not recursive by default.
-    let mut stale = Vec::new();
-    let mut fresh = Vec::new();
-    let mut scanned = 0;
+    let started = Instant::now();
 
-    for entry in fs::read_dir(&root).with_context(|| format!("read_dir failed: {}", root.display()))?
-    {
-        let entry = entry?;
-        let path = entry.path();
+    // Sorted, so the report stays stable no matter which worker finishes first.
+    let discovered = discover_projects(&root, opts.discover_depth, &opts.filter)?;
+    let mut excluded = discovered.excluded;
+    let mut projects = Vec::new();
+    for path in discovered.projects {
+        let kind = ProjectKind::detect(&path);
+        if !opts.kinds.is_empty() && !opts.kinds.contains(&kind) {
+            continue;
+        }
+        match filter::load_ignore_or_skip(&path) {
+            Ok(ignore) => projects.push((path, kind, ignore)),
+            Err(rule) => excluded.push(Excluded { path, rule }),
+        }
+    }
 
-        // Skip non-directories
-        if !path.is_dir() {
+    let items = parallel_map(&projects, opts.jobs, |(path, kind, ignore)| {
+        let stop_after = opts.early_exit.then_some(cutoff);
+        let ignore = ignore.as_ref();
+        let (newest, reused) = if opts.cache {
+            let walked = cache::newest_cached(path, opts, stop_after, ignore);
+            (walked.newest, walked.reused)
+        } else {
+            (walk_tree(path, opts, stop_after, ignore), false)
+        };
+        let (mut last_modified, mut activity_source) = last_activity(path, opts, newest);
+
+        // Only stale projects are sized: fresh ones aren't acted on, and their
+        // node_modules and target folders are where most of the files are. The
+        // index may have missed a file rewritten in place, so a project it
+        // finds stale is walked again from disk first.
+        let mut size = None;
+        if last_modified <= cutoff && (opts.sizes || reused) {
+            if opts.cache {
+                let walked = cache::walk_cached(path, opts, stop_after, ignore);
+                (last_modified, activity_source) = last_activity(path, opts, walked.newest);
+                size = walked.size;
+            } else {
+                size = Some(dir_size(path));
+            }
+        }
+        ProjectItem {
+            path: path.clone(),
+            kind: *kind,
+            vcs: Vcs::detect(path),
+            last_modified,
+            activity_source,
+            size: size.filter(|_| last_modified <= cutoff),
+        }
+    });
+
+    let (stale, fresh): (Vec<ProjectItem>, Vec<ProjectItem>) = items
+        .into_iter()
+        .partition(|item| item.last_modified <= cutoff);
+
+    let mut report = ScanReport {
+        roots: vec![RootScan {
+            path: root,
+            older_than_days,
+            scanned_count: projects.len(),
+            duration: started.elapsed(),
+        }],
+        stale,
+        fresh,
+        excluded,
+        sort: opts.sort,
+    };
+    // oldest first by default for nicer output
+    report.sort_by(opts.sort);
+
+    Ok(report)
+}
+
+// Canonical forms of `roots`, each listed once. Returns the roots to scan and
+// the duplicates that were dropped. A root inside another is an error: its
+// projects would be found twice, or the inner root taken for a project.
+pub fn distinct_roots(roots: &[PathBuf]) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let mut kept: Vec<PathBuf> = Vec::new();
+    let mut duplicates = Vec::new();
+    for root in roots {
+        let canonical = root
+            .canonicalize()
+            .with_context(|| format!("Cannot access path: {}", root.display()))?;
+        if kept.contains(&canonical) {
+            duplicates.push(root.clone());
             continue;
         }
+        if let Some(other) = kept
+            .iter()
+            .find(|k| canonical.starts_with(k) || k.starts_with(&canonical))
+        {
+            let (inner, outer) = if canonical.starts_with(other) {
+                (&canonical, other)
+            } else {
+                (other, &canonical)
+            };
+            bail!(
+                "Roots overlap: {} is inside {}; scan one or the other",
+                inner.display(),
+                outer.display()
+            );
+        }
+        kept.push(canonical);
+    }
+    Ok((kept, duplicates))
+}
 
-        // Optional: skip hidden dirs
-        if let Some(name) = path.file_name().and_then(|s| s.to_str()) {
-            if name.starts_with('.') {
-                continue;
+// Project folders under `root`, down to `max_depth` levels. A folder that is a
+// project root (see `kind::is_project_root`) is taken as is and not descended
+// into. Any other folder is searched only if there is a boundary somewhere
+// below it within `max_depth`: a project root, or a folder `filter` rules out.
+// Otherwise it is taken whole, so a plain folder of notes isn't split into its
+// subfolders. Hidden folders are skipped, symlinked ones are never followed
+// further down, and whatever `filter` rules out is set aside with the rule
+// that did it.
+pub fn discover_projects(root: &Path, max_depth: usize, filter: &Filter) -> Result<Discovered> {
+    let mut found = Discovered::default();
+    let mut ignores: Vec<Gitignore> = match filter::load_ignore_or_skip(root) {
+        Ok(ignore) => ignore.into_iter().collect(),
+        Err(rule) => {
+            found.excluded.push(Excluded {
+                path: root.to_path_buf(),
+                rule,
+            });
+            return Ok(found);
+        }
+    };
+    let entries =
+        fs::read_dir(root).with_context(|| format!("read_dir failed: {}", root.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        discover_in(
+            root,
+            path,
+            1,
+            max_depth.max(1),
+            filter,
+            &mut ignores,
+            &mut found,
+        )?;
+    }
+    found.projects.sort();
+    found.excluded.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(found)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Discovered {
+    pub projects: Vec<PathBuf>,
+    pub excluded: Vec<Excluded>,
+}
+
+impl Discovered {
+    fn take(&mut self, root: &Path, path: PathBuf, filter: &Filter) {
+        match filter.not_included(root, &path) {
+            None => self.projects.push(path),
+            Some(rule) => self.excluded.push(Excluded { path, rule }),
+        }
+    }
+}
+
+fn discover_in(
+    root: &Path,
+    path: PathBuf,
+    depth: usize,
+    max_depth: usize,
+    filter: &Filter,
+    ignores: &mut Vec<Gitignore>,
+    found: &mut Discovered,
+) -> Result<()> {
+    // Skip non-directories
+    if !path.is_dir() {
+        return Ok(());
+    }
+
+    // Optional: skip hidden dirs
+    if let Some(name) = path.file_name().and_then(|s| s.to_str())
+        && name.starts_with('.')
+    {
+        return Ok(());
+    }
+    if let Some(rule) = filter.excluded(root, &path, true, ignores) {
+        found.excluded.push(Excluded { path, rule });
+        return Ok(());
+    }
+
+    let is_link = path.symlink_metadata().is_ok_and(|m| m.is_symlink());
+    if depth >= max_depth || is_link || kind::is_project_root(&path) {
+        found.take(root, path, filter);
+        return Ok(());
+    }
+
+    let before = found.projects.len() + found.excluded.len();
+    // Only worth searching if something below draws a line. The folder's own
+    // `.sweeperignore` doesn't count: if nothing else does, the folder is a
+    // project and the file is about its activity walk.
+    let boundary = WalkDir::new(&path)
+        .min_depth(1)
+        .max_depth(max_depth - depth)
+        .into_iter()
+        .filter_entry(|e| {
+            e.file_type().is_dir() && !e.file_name().to_string_lossy().starts_with('.')
+        })
+        .flatten()
+        .any(|e| {
+            kind::is_project_root(e.path())
+                || filter.excluded(root, e.path(), true, ignores).is_some()
+        });
+    let mut has_own = false;
+    if boundary && let Ok(entries) = fs::read_dir(&path) {
+        let own = match filter::load_ignore_or_skip(&path) {
+            Ok(own) => own,
+            Err(rule) => {
+                found.excluded.push(Excluded { path, rule });
+                return Ok(());
             }
+        };
+        has_own = own.is_some();
+        ignores.extend(own);
+        for entry in entries.flatten() {
+            discover_in(
+                root,
+                entry.path(),
+                depth + 1,
+                max_depth,
+                filter,
+                ignores,
+                found,
+            )?;
         }
+    }
+    if has_own {
+        ignores.pop();
+    }
+    // A plain folder with nothing inside found or ruled out is most likely a
+    // project itself.
+    if found.projects.len() + found.excluded.len() == before {
+        found.take(root, path, filter);
+    }
+    Ok(())
+}
 
-        scanned += 1;
+// `f` over every input on up to `jobs` threads, results in input order. Workers
+// take the next unclaimed input as they free up, so one huge project doesn't
+// hold up a whole batch.
+fn parallel_map<T, R, F>(inputs: &[T], jobs: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let jobs = jobs.clamp(1, inputs.len().max(1));
+    if jobs == 1 {
+        return inputs.iter().map(f).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, R)> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(input) = inputs.get(i) else {
+                            return done;
+                        };
+                        done.push((i, f(input)));
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|w| w.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+            .collect()
+    });
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, r)| r).collect()
+}
 
-        // Determine "last modified" of the folder by looking at the newest file inside it.
-        let last_modified = newest_mtime_in_tree(&path).unwrap_or_else(|| {
+fn last_activity(
+    path: &Path,
+    opts: &ScanOptions,
+    newest_mtime: Option<SystemTime>,
+) -> (SystemTime, ActivitySource) {
+    let mtime = || {
+        // "last modified" of the folder is the newest file inside it.
+        let t = newest_mtime.unwrap_or_else(|| {
             // fallback: folder metadata mtime
-            fs::metadata(&path)
+            fs::metadata(path)
                 .and_then(|m| m.modified())
                 .unwrap_or(SystemTime::UNIX_EPOCH)
         });
+        (t, ActivitySource::Mtime)
+    };
 
-        let item = ProjectItem { path, last_modified };
+    let from_commit = || {
+        git::is_repo(path)
+            .then(|| git::last_commit_time(path))
+            .flatten()
+            .map(|t| (t, ActivitySource::GitCommit))
+    };
 
-        if last_modified <= cutoff {
-            stale.push(item);
-        } else {
-            fresh.push(item);
+    // Checkouts and rebases count too, which is opt-in: they also happen when
+    // nobody is working on the project.
+    let from_git_any = || {
+        if !git::is_repo(path) {
+            return None;
         }
-    }
-
-    // sort stale oldest first for nicer output
-    stale.sort_by_key(|p| p.last_modified);
+        // Listed weakest first: on a tie max_by_key keeps the last, i.e. the commit.
+        [
+            (git::head_changed_time(path), ActivitySource::GitHead),
+            (git::last_reflog_time(path), ActivitySource::GitReflog),
+            (git::last_commit_time(path), ActivitySource::GitCommit),
+        ]
+        .into_iter()
+        .filter_map(|(t, src)| t.map(|t| (t, src)))
+        .max_by_key(|(t, _)| *t)
+    };
 
-    Ok(ScanReport {
-        root,
-        older_than_days,
-        stale,
-        fresh,
-        scanned_count: scanned,
-    })
+    match opts.activity {
+        ActivityMode::Mtime => mtime(),
+        ActivityMode::Git => from_commit().unwrap_or_else(mtime),
+        ActivityMode::GitAny => from_git_any().unwrap_or_else(mtime),
+        ActivityMode::Max => {
+            let m = mtime();
+            match from_commit() {
+                Some(g) if g.0 > m.0 => g,
+                _ => m,
+            }
+        }
+    }
 }
 
-fn newest_mtime_in_tree(dir: &Path) -> Option<SystemTime> {
+// The newest mtime in a project, within `opts.depth` and outside ignored dirs,
+// `.git` (which git rewrites on its own, e.g. on `git status`) and whatever the
+// project's `.sweeperignore` matches.
+pub(crate) fn walk_tree(
+    dir: &Path,
+    opts: &ScanOptions,
+    stop_after: Option<SystemTime>,
+    ignore: Option<&Gitignore>,
+) -> Option<SystemTime> {
     let mut newest: Option<SystemTime> = None;
 
-    for e in WalkDir::new(dir)
-        .max_depth(3) // keep it fast; change to higher if you want
-        .into_iter()
-        .filter_map(|x| x.ok())
-    {
-        if let Ok(meta) = e.metadata() {
-            if let Ok(mtime) = meta.modified() {
-                newest = match newest {
-                    None => Some(mtime),
-                    Some(cur) => Some(cur.max(mtime)),
-                };
+    let max_depth = match opts.depth {
+        Depth::Limited(n) => n,
+        Depth::Unlimited => usize::MAX,
+    };
+
+    let is_ignored = |e: &walkdir::DirEntry| {
+        let is_dir = e.file_type().is_dir();
+        // depth 0 is the project itself, which may well be called `build`.
+        e.depth() > 0
+            && (e.file_name() == ".git"
+                || (is_dir
+                    && opts
+                        .ignore_dirs
+                        .iter()
+                        .any(|name| e.file_name() == name.as_str()))
+                || ignore.is_some_and(|i| i.matched(e.path(), is_dir).is_ignore()))
+    };
+
+    let mut it = WalkDir::new(dir).max_depth(max_depth).into_iter();
+    while let Some(e) = it.next() {
+        let Ok(e) = e else { continue };
+
+        if is_ignored(&e) {
+            // On a file this would skip the rest of its folder.
+            if e.file_type().is_dir() {
+                it.skip_current_dir();
+            }
+            continue;
+        }
+
+        let Ok(meta) = e.metadata() else { continue };
+
+        if let Ok(mtime) = meta.modified() {
+            newest = match newest {
+                None => Some(mtime),
+                Some(cur) => Some(cur.max(mtime)),
+            };
+
+            // Anything newer than the cutoff settles it: the project is fresh.
+            if stop_after.is_some_and(|cutoff| mtime > cutoff) {
+                break;
             }
         }
     }
@@ -116,7 +746,79 @@ fn newest_mtime_in_tree(dir: &Path) -> Option<SystemTime> {
     newest
 }
 
-pub fn build_archive_plan(report: &ScanReport, dest_root: &Path) -> Result<ArchivePlan> {
+// Size of everything under `path`, counted like a project's size.
+pub(crate) fn dir_size(path: &Path) -> ProjectSize {
+    let mut size = ProjectSize::default();
+    let mut seen_inodes = HashSet::new();
+    for entry in WalkDir::new(path).follow_links(false).into_iter().flatten() {
+        if let Ok(meta) = entry.metadata() {
+            add_entry_size(&mut size, &meta, &mut seen_inodes);
+        }
+    }
+    size
+}
+
+#[cfg(unix)]
+fn add_entry_size(size: &mut ProjectSize, meta: &fs::Metadata, seen: &mut HashSet<(u64, u64)>) {
+    use std::os::unix::fs::MetadataExt;
+
+    if !meta.is_dir() && meta.nlink() > 1 && !seen.insert((meta.dev(), meta.ino())) {
+        return;
+    }
+    if meta.is_file() {
+        size.file_count += 1;
+    }
+    if !meta.is_dir() {
+        size.apparent_bytes += meta.len();
+    }
+    size.disk_bytes += meta.blocks() * 512;
+}
+
+#[cfg(not(unix))]
+fn add_entry_size(size: &mut ProjectSize, meta: &fs::Metadata, _seen: &mut HashSet<(u64, u64)>) {
+    if meta.is_file() {
+        size.file_count += 1;
+        size.apparent_bytes += meta.len();
+        size.disk_bytes += meta.len();
+    }
+}
+
+// A stale project kept out of archive/delete because it still holds unsaved work.
+#[derive(Debug, Clone)]
+pub struct HeldBack {
+    pub item: ProjectItem,
+    pub reasons: Vec<String>,
+}
+
+// Removes git checkouts with uncommitted changes, stashes or unpushed/local-only
+// branches from `report.stale` and returns them with the reasons.
+pub fn hold_back_unsaved_work(report: &mut ScanReport) -> Vec<HeldBack> {
+    let mut held = Vec::new();
+
+    report.stale.retain(|item| {
+        if !git::is_repo(&item.path) {
+            return true;
+        }
+        let reasons = git::unsaved_work(&item.path);
+        if reasons.is_empty() {
+            return true;
+        }
+        held.push(HeldBack {
+            item: item.clone(),
+            reasons,
+        });
+        false
+    });
+
+    held
+}
+
+pub fn build_archive_plan(
+    report: &ScanReport,
+    dest_root: &Path,
+    compression: Compression,
+    checksum: Option<Algorithm>,
+) -> Result<ArchivePlan> {
     let dest_root = dest_root
         .to_path_buf()
         .canonicalize()
@@ -141,42 +843,70 @@ pub fn build_archive_plan(report: &ScanReport, dest_root: &Path) -> Result<Archi
             .map(|s| s.to_string_lossy().to_string())
             .unwrap_or_else(|| "unknown".to_string());
 
-        let mut to = bucket_dir.join(&name);
-        to = avoid_collision(&to);
+        let to = match compression.extension() {
+            None => avoid_collision(&bucket_dir.join(&name)),
+            // Number the name, not the extension: `foo_1.tar.zst`.
+            Some(ext) => {
+                let mut to = bucket_dir.join(format!("{}.{}", name, ext));
+                let mut i = 1;
+                while to.exists() {
+                    to = bucket_dir.join(format!("{}_{}.{}", name, i, ext));
+                    i += 1;
+                }
+                to
+            }
+        };
 
         moves.push(ArchiveMove {
             from: item.path.clone(),
             to,
+            item: item.clone(),
         });
     }
 
     Ok(ArchivePlan {
         dest_root,
         month_bucket: bucket,
+        compression,
+        checksum,
         moves,
     })
 }
 
-pub fn apply_archive_plan(plan: &ArchivePlan) -> Result<()> {
+pub fn apply_archive_plan(plan: &ArchivePlan, run: &mut journal::Run) -> Result<()> {
     for mv in &plan.moves {
         if let Some(parent) = mv.to.parent() {
             fs::create_dir_all(parent)
                 .with_context(|| format!("Failed to create dir: {}", parent.display()))?;
         }
 
-        // rename = move (on same filesystem). If different filesystem, you’d need copy+delete.
-        fs::rename(&mv.from, &mv.to).with_context(|| {
-            format!(
-                "Failed to move '{}' -> '{}'",
-                mv.from.display(),
-                mv.to.display()
-            )
-        })?;
+        // Taken from the source before anything moves; the copy or tarball
+        // has to match them before the source is removed.
+        let sums = plan
+            .checksum
+            .map(|a| checksum::hash_tree(&mv.from, a))
+            .transpose()?;
+
+        if plan.compression == Compression::None {
+            // rename when possible, copy + verify + delete across filesystems.
+            mover::move_path(&mv.from, &mv.to, sums.as_ref())?;
+            run.record_move(&mv.from, &mv.to);
+        } else {
+            // pack, read back and compare, then delete the folder.
+            compress::pack(&mv.from, &mv.to, plan.compression, sums.as_ref())?;
+            run.record_pack(&mv.from, &mv.to);
+        }
+
+        match &sums {
+            Some(sums) => checksum::save(&mv.to, sums)?,
+            None => checksum::remove(&mv.to)?,
+        }
+        manifest::record(&mv.to, &mv.item, plan.compression, plan.checksum)?;
     }
     Ok(())
 }
 
-fn avoid_collision(target: &Path) -> PathBuf {
+pub(crate) fn avoid_collision(target: &Path) -> PathBuf {
     if !target.exists() {
         return target.to_path_buf();
     }
@@ -191,22 +921,95 @@ fn avoid_collision(target: &Path) -> PathBuf {
 }
 
 pub fn print_report(report: &ScanReport) {
-    println!("Root: {}", report.root.display());
-    println!("Scanned project folders: {}", report.scanned_count);
-    println!("Stale threshold: {} days\n", report.older_than_days);
+    // Numbered across roots, so every project has its own number.
+    let mut idx = 0;
+    for (i, root) in report.roots.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        let stale: Vec<&ProjectItem> = report
+            .stale
+            .iter()
+            .filter(|p| report.root_of(p).is_some_and(|r| r.path == root.path))
+            .collect();
+        print_root(root, &stale, report.sort, &mut idx);
+    }
+
+    if report.roots.len() > 1 {
+        let total = report.reclaimable();
+        println!(
+            "\nAll {} roots: {} project folders scanned in {:.1}s, {} stale, {} reclaimable",
+            report.roots.len(),
+            report.scanned_count(),
+            report.duration().as_secs_f64(),
+            report.stale.len(),
+            fmt_bytes(total.disk_bytes)
+        );
+    }
+}
+
+fn print_root(root: &RootScan, stale: &[&ProjectItem], sort: SortKey, idx: &mut usize) {
+    println!("Root: {}", root.path.display());
+    println!(
+        "Scanned project folders: {} in {:.1}s",
+        root.scanned_count,
+        root.duration.as_secs_f64()
+    );
+    println!("Stale threshold: {} days\n", root.older_than_days);
 
-    if report.stale.is_empty() {
+    if stale.is_empty() {
         println!("No stale folders found. ✅");
         return;
     }
 
-    println!("Stale folders (oldest first):");
-    for (idx, item) in report.stale.iter().enumerate() {
+    match sort {
+        SortKey::Age => println!("Stale folders by kind (oldest first):"),
+        SortKey::Size => println!("Stale folders by kind (largest first):"),
+    }
+    let mut kinds: Vec<ProjectKind> = stale.iter().map(|p| p.kind).collect();
+    kinds.sort();
+    kinds.dedup();
+
+    for kind in kinds {
+        let items: Vec<&ProjectItem> = stale.iter().copied().filter(|p| p.kind == kind).collect();
+        let disk: u64 = items
+            .iter()
+            .filter_map(|p| p.size)
+            .map(|s| s.disk_bytes)
+            .sum();
+        println!("\n{} ({}, {}):", kind.label(), items.len(), fmt_bytes(disk));
+        for item in items {
+            *idx += 1;
+            let size = item
+                .size
+                .map(|s| format!(", {}, {} files", fmt_bytes(s.disk_bytes), s.file_count))
+                .unwrap_or_default();
+            let vcs = item
+                .vcs
+                .map(|v| format!(", {} checkout", v.label()))
+                .unwrap_or_default();
+            println!(
+                "  {:>2}. {}  (last modified: {} via {}{}{})",
+                idx,
+                item.path.display(),
+                fmt_time(item.last_modified),
+                item.activity_source.label(),
+                size,
+                vcs
+            );
+        }
+    }
+
+    let mut total = ProjectSize::default();
+    for size in stale.iter().filter_map(|p| p.size.as_ref()) {
+        total.add(size);
+    }
+    if stale.iter().any(|p| p.size.is_some()) {
         println!(
-            "  {:>2}. {}  (last modified: {})",
-            idx + 1,
-            item.path.display(),
-            fmt_time(item.last_modified)
+            "\nReclaimable: {} on disk ({} apparent, {} files)",
+            fmt_bytes(total.disk_bytes),
+            fmt_bytes(total.apparent_bytes),
+            total.file_count
         );
     }
 }
@@ -214,6 +1017,15 @@ pub fn print_report(report: &ScanReport) {
 pub fn print_plan(plan: &ArchivePlan) {
     println!("Archive destination: {}", plan.dest_root.display());
     println!("Month bucket: {}", plan.month_bucket);
+    if let Some(ext) = plan.compression.extension() {
+        println!(
+            "Packing into: .{} (verified before the folder is removed)",
+            ext
+        );
+    }
+    if let Some(a) = plan.checksum {
+        println!("Checksums: {} (recorded for `sweeper verify`)", a.label());
+    }
     println!("Planned moves: {}\n", plan.moves.len());
 
     for (idx, mv) in plan.moves.iter().enumerate() {
@@ -226,76 +1038,467 @@ pub fn print_plan(plan: &ArchivePlan) {
     }
 }
 
+pub fn print_held_back(held: &[HeldBack]) {
+    if held.is_empty() {
+        return;
+    }
+
+    println!("Held back (unsaved work, use --force-dirty to include):");
+    for h in held {
+        println!("  - {}", h.item.path.display());
+        for reason in &h.reasons {
+            println!("      {}", reason);
+        }
+    }
+    println!();
+}
+
 fn fmt_time(t: SystemTime) -> String {
     let dt: DateTime<Local> = t.into();
     dt.format("%Y-%m-%d %H:%M").to_string()
 }
 
-pub fn organize_folder(path: &std::path::Path, dry_run: bool) -> anyhow::Result<()> {
-    use std::fs;
+pub fn fmt_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OrganizeMove {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub category: String,
+    pub reason: String,
+    // `to` already exists with the same content, so the file stays where it is.
+    pub identical: bool,
+}
 
-    let categories = |ext: &str| -> &str {
-        match ext {
-            "pdf" | "doc" | "docx" | "txt" => "Documents",
-            "jpg" | "png" | "gif" | "webp" => "Images",
-            "zip" | "rar" | "7z" | "tar" | "gz" => "Archives",
-            "dmg" | "exe" | "msi" | "pkg" | "deb" | "rpm" => "Installers",
-            "csv" | "xlsx" => "Spreadsheets",
-            _ => "Other",
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OrganizeOptions {
+    // Also classify files by their content.
+    pub sniff: bool,
+    // Don't move a file whose twin already sits in the target folder.
+    pub skip_identical: bool,
+}
+
+// Decide where each file directly inside `path` goes, using the first matching
+// rule. Files `filter` or the folder's `.sweeperignore` rule out are returned
+// separately. Nothing is touched on disk.
+pub fn plan_organize(
+    path: &Path,
+    rules: &RuleSet,
+    opts: &OrganizeOptions,
+    filter: &Filter,
+) -> Result<(Vec<OrganizeMove>, Vec<Excluded>)> {
+    let mut moves = Vec::new();
+    let mut excluded = Vec::new();
+    // Targets already handed out in this plan, so two files can't be sent to the
+    // same name before either of them exists on disk.
+    let mut taken = HashSet::new();
+    let ignores: Vec<Gitignore> = match filter::load_ignore_or_skip(path) {
+        Ok(ignore) => ignore.into_iter().collect(),
+        Err(rule) => {
+            let path = path.to_path_buf();
+            return Ok((Vec::new(), vec![Excluded { path, rule }]));
         }
     };
 
     for entry in fs::read_dir(path)? {
-        let entry = entry?;
-        let file_path = entry.path();
-
-        if !file_path.is_file() {
+        let file_path = entry?.path();
+        if file_path.is_file()
+            && let Some(rule) = filter.check(path, &file_path, false, &ignores)
+        {
+            excluded.push(Excluded {
+                path: file_path,
+                rule,
+            });
             continue;
         }
+        if let Some(mv) = plan_organize_file(path, &file_path, rules, opts, &mut taken)? {
+            moves.push(mv);
+        }
+    }
 
-        let ext = file_path
-            .extension()
-            .and_then(|s| s.to_str())
-            .unwrap_or("")
-            .to_lowercase();
+    excluded.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok((moves, excluded))
+}
 
-        let category = categories(&ext);
-        let target_dir = path.join(category);
+// Plan the move for one file directly inside `root`. None for things organize
+// leaves alone: non-files and our own per-folder config and ignore file.
+pub fn plan_organize_file(
+    root: &Path,
+    file_path: &Path,
+    rules: &RuleSet,
+    opts: &OrganizeOptions,
+    taken: &mut HashSet<PathBuf>,
+) -> Result<Option<OrganizeMove>> {
+    let Some(file_name) = file_path.file_name() else {
+        return Ok(None);
+    };
+    if !file_path.is_file()
+        || file_name == config::ROOT_FILE_NAME
+        || file_name == filter::IGNORE_FILE
+    {
+        return Ok(None);
+    }
 
-        let file_name = file_path.file_name().unwrap();
-        let mut target_path = target_dir.join(file_name);
+    let facts = FileFacts::from_path(file_path, opts.sniff)?;
+    let placement = rules.place(&facts);
+    let target_dir = root.join(&placement.target);
 
-        // Avoid overwrite
-        let mut counter = 1;
-        while target_path.exists() {
-            let new_name = format!(
-                "{}_{}",
-                file_name.to_string_lossy(),
-                counter
-            );
-            target_path = target_dir.join(new_name);
-            counter += 1;
+    let mut target_path = target_dir.join(file_name);
+
+    // Avoid overwrite. Every name we step over may already be a copy of this
+    // very file (an earlier `name_1`, say).
+    let mut counter = 1;
+    while target_path.exists() || taken.contains(&target_path) {
+        if opts.skip_identical
+            && target_path.is_file()
+            && dupes::same_content(file_path, &target_path).unwrap_or(false)
+        {
+            return Ok(Some(OrganizeMove {
+                from: file_path.to_path_buf(),
+                to: target_path,
+                category: placement.target.to_string_lossy().to_string(),
+                reason: format!("{}; identical copy already there", placement.reason),
+                identical: true,
+            }));
         }
+        let new_name = format!("{}_{}", file_name.to_string_lossy(), counter);
+        target_path = target_dir.join(new_name);
+        counter += 1;
+    }
+    taken.insert(target_path.clone());
 
-        println!("Move: '{}' -> '{}'", file_path.display(), target_path.display());
+    Ok(Some(OrganizeMove {
+        from: file_path.to_path_buf(),
+        to: target_path,
+        category: placement.target.to_string_lossy().to_string(),
+        reason: placement.reason,
+        identical: false,
+    }))
+}
+
+// A file whose extension doesn't fit its content.
+#[derive(Debug, Clone)]
+pub struct Mismatch {
+    pub path: PathBuf,
+    pub extension: String,
+    pub content: sniff::Kind,
+}
 
-        if !dry_run {
-            fs::create_dir_all(&target_dir)?;
-            fs::rename(&file_path, &target_path)?;
+// Files directly inside `path` whose content was recognized and disagrees with
+// their extension (including files with no extension at all).
+pub fn find_mismatches(path: &Path) -> Result<Vec<Mismatch>> {
+    let mut found = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let file_path = entry?.path();
+        if !file_path.is_file() {
+            continue;
+        }
+        let facts = FileFacts::from_path(&file_path, true)?;
+        if let Some(content) = facts.mismatch() {
+            found.push(Mismatch {
+                path: file_path,
+                extension: facts.name_ext,
+                content,
+            });
         }
     }
+    found.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(found)
+}
 
-    if dry_run {
-        println!("\nDry-run only. Use without --dry-run to apply.");
+pub fn print_mismatches(found: &[Mismatch]) {
+    if found.is_empty() {
+        println!("Every recognized file has a matching extension.");
+        return;
+    }
+    for m in found {
+        let ext = if m.extension.is_empty() {
+            "no extension".to_string()
+        } else {
+            format!("'.{}'", m.extension)
+        };
+        println!(
+            "{}: {} but content is {}",
+            m.path.display(),
+            ext,
+            m.content.name
+        );
     }
+}
 
+pub fn apply_organize(moves: &[OrganizeMove], run: &mut journal::Run) -> Result<()> {
+    for mv in moves.iter().filter(|mv| !mv.identical) {
+        if let Some(parent) = mv.to.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create dir: {}", parent.display()))?;
+        }
+        fs::rename(&mv.from, &mv.to).with_context(|| {
+            format!(
+                "Failed to move '{}' -> '{}'",
+                mv.from.display(),
+                mv.to.display()
+            )
+        })?;
+        run.record_move(&mv.from, &mv.to);
+    }
     Ok(())
 }
 
-pub fn delete_to_trash(items: &[ProjectItem]) -> anyhow::Result<()> {
+pub fn print_organize(moves: &[OrganizeMove]) {
+    for mv in moves {
+        if mv.identical {
+            println!(
+                "Skip: '{}' (identical to '{}')",
+                mv.from.display(),
+                mv.to.display()
+            );
+        } else {
+            println!("Move: '{}' -> '{}'", mv.from.display(), mv.to.display());
+        }
+    }
+}
+
+pub fn delete_to_trash(items: &[ProjectItem], run: &mut journal::Run) -> anyhow::Result<()> {
     for item in items {
         trash::delete(&item.path)
             .with_context(|| format!("Failed to move '{}' to trash", item.path.display()))?;
+        run.record_trash(&item.path);
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mkfile(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+
+    fn discovered(root: &Path, max_depth: usize, filter: &Filter) -> Vec<String> {
+        let found = discover_projects(root, max_depth, filter).unwrap();
+        found
+            .projects
+            .iter()
+            .map(|p| p.strip_prefix(root).unwrap().display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn plain_folders_are_not_split_into_their_subfolders() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        mkfile(&root.join("org/app/Cargo.toml"));
+        mkfile(&root.join("org/notes/src/a.md"));
+        mkfile(&root.join("org/notes/docs/b.md"));
+        mkfile(&root.join("scratch/one/x.txt"));
+        mkfile(&root.join("scratch/two/y.txt"));
+        // A project hidden away doesn't make its parent a container.
+        mkfile(&root.join("dotted/.cache/pkg/package.json"));
+        mkfile(&root.join("dotted/data/z.csv"));
+
+        assert_eq!(
+            discovered(root, 4, &Filter::default()),
+            ["dotted", "org/app", "org/notes", "scratch"]
+        );
+    }
+
+    #[test]
+    fn boundaries_past_the_depth_limit_are_not_looked_for() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        mkfile(&root.join("deep/x/y/Cargo.toml"));
+        mkfile(&root.join("deep/x/notes.txt"));
+
+        assert_eq!(discovered(root, 2, &Filter::default()), ["deep"]);
+        assert_eq!(discovered(root, 3, &Filter::default()), ["deep/x/y"]);
+    }
+
+    #[test]
+    fn excluded_folders_are_boundaries_too() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        mkfile(&root.join("work/keep/a.txt"));
+        mkfile(&root.join("work/secret/b.txt"));
+
+        let filter = Filter::new(&[], &["secret".to_string()]).unwrap();
+        let found = discover_projects(root, 3, &filter).unwrap();
+        assert_eq!(found.projects, [root.join("work/keep")]);
+        assert_eq!(found.excluded.len(), 1);
+        assert_eq!(found.excluded[0].path, root.join("work/secret"));
+    }
+
+    #[test]
+    fn a_folders_own_ignore_file_only_applies_below_a_container() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        // A plain folder: its ignore file is for the activity walk.
+        mkfile(&root.join("notes/drafts/a.md"));
+        mkfile(&root.join("notes/cache/b.bin"));
+        fs::write(root.join("notes/.sweeperignore"), "cache/\n").unwrap();
+        // A folder of projects: its ignore file keeps projects out.
+        mkfile(&root.join("work/app/Cargo.toml"));
+        mkfile(&root.join("work/old/package.json"));
+        fs::write(root.join("work/.sweeperignore"), "old/\n").unwrap();
+
+        let found = discover_projects(root, 3, &Filter::default()).unwrap();
+        assert_eq!(found.projects, [root.join("notes"), root.join("work/app")]);
+        assert_eq!(found.excluded.len(), 1);
+        assert_eq!(found.excluded[0].path, root.join("work/old"));
+    }
+
+    #[test]
+    fn a_broken_ignore_file_only_sets_its_own_folder_aside() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        mkfile(&root.join("ok/Cargo.toml"));
+        mkfile(&root.join("bad/Cargo.toml"));
+        fs::write(root.join("bad/.sweeperignore"), "{oops\n").unwrap();
+        mkfile(&root.join("org/app/go.mod"));
+        fs::write(root.join("org/.sweeperignore"), "{oops\n").unwrap();
+
+        let opts = ScanOptions {
+            cache: false,
+            discover_depth: 2,
+            ..ScanOptions::default()
+        };
+        let report = scan_projects(root, &opts).unwrap();
+        let name = |p: &Path| p.file_name().unwrap().to_string_lossy().to_string();
+        let scanned: Vec<_> = report
+            .stale
+            .iter()
+            .chain(&report.fresh)
+            .map(|i| name(&i.path))
+            .collect();
+        assert_eq!(scanned, ["ok"]);
+        let mut excluded: Vec<_> = report.excluded.iter().map(|e| name(&e.path)).collect();
+        excluded.sort();
+        assert_eq!(excluded, ["bad", "org"]);
+        assert!(report.excluded[0].rule.contains(".sweeperignore"));
+
+        fs::write(root.join(".sweeperignore"), "{oops\n").unwrap();
+        let found = discover_projects(root, 2, &Filter::default()).unwrap();
+        assert!(found.projects.is_empty());
+        assert_eq!(found.excluded[0].path, root);
+    }
+
+    fn run_git(dir: &Path, args: &[&str]) {
+        let ok = std::process::Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=t", "-c", "user.email=t@example.com"])
+            .args(args)
+            .output()
+            .unwrap()
+            .status
+            .success();
+        assert!(ok, "git {:?} failed", args);
+    }
+
+    // A clone of a fresh repo, in sync with its upstream.
+    fn checkout(tmp: &Path, name: &str) -> PathBuf {
+        let origin = tmp.join(format!("{}.git", name));
+        let work = tmp.join(name);
+        run_git(tmp, &["init", "-q", "--bare", &origin.to_string_lossy()]);
+        run_git(
+            tmp,
+            &[
+                "clone",
+                "-q",
+                &origin.to_string_lossy(),
+                &work.to_string_lossy(),
+            ],
+        );
+        fs::write(work.join("README"), "hi\n").unwrap();
+        run_git(&work, &["add", "README"]);
+        run_git(&work, &["commit", "-q", "-m", "first"]);
+        run_git(&work, &["push", "-q", "-u", "origin", "HEAD"]);
+        work
+    }
+
+    fn stale(paths: &[&Path]) -> ScanReport {
+        let item = |path: &&Path| ProjectItem {
+            path: path.to_path_buf(),
+            kind: ProjectKind::Plain,
+            vcs: Some(Vcs::Git),
+            last_modified: SystemTime::UNIX_EPOCH,
+            activity_source: ActivitySource::Mtime,
+            size: None,
+        };
+        ScanReport {
+            roots: Vec::new(),
+            stale: paths.iter().map(item).collect(),
+            fresh: Vec::new(),
+            excluded: Vec::new(),
+            sort: SortKey::Age,
+        }
+    }
+
+    #[test]
+    fn checkouts_with_unsaved_work_are_held_back() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        let clean = checkout(root, "clean");
+        let dirty = checkout(root, "dirty");
+        fs::write(dirty.join("new.txt"), "draft").unwrap();
+        let stashed = checkout(root, "stashed");
+        fs::write(stashed.join("README"), "changed\n").unwrap();
+        run_git(&stashed, &["stash", "-q"]);
+        let ahead = checkout(root, "ahead");
+        fs::write(ahead.join("README"), "changed\n").unwrap();
+        run_git(&ahead, &["commit", "-q", "-am", "second"]);
+
+        let mut report = stale(&[&clean, &dirty, &stashed, &ahead]);
+        let held = hold_back_unsaved_work(&mut report);
+
+        let kept: Vec<_> = report.stale.iter().map(|i| i.path.clone()).collect();
+        assert_eq!(kept, [clean]);
+        let reasons: Vec<_> = held
+            .iter()
+            .map(|h| (h.item.path.clone(), h.reasons.join("; ")))
+            .collect();
+        assert_eq!(reasons.len(), 3);
+        assert_eq!(reasons[0].0, dirty);
+        assert!(reasons[0].1.contains("uncommitted or untracked"));
+        assert_eq!(reasons[1].0, stashed);
+        assert!(reasons[1].1.contains("1 stash(es)"));
+        assert!(!reasons[1].1.contains("uncommitted"));
+        assert_eq!(reasons[2].0, ahead);
+        assert!(reasons[2].1.contains("1 unpushed commit(s)"));
+    }
+
+    #[test]
+    fn checking_for_unsaved_work_is_not_activity() {
+        let tmp = tempfile::tempdir().unwrap();
+        let work = checkout(tmp.path(), "app");
+        let old = filetime::FileTime::from_unix_time(1_600_000_000, 0);
+        for e in walkdir::WalkDir::new(&work) {
+            filetime::set_symlink_file_times(e.unwrap().path(), old, old).unwrap();
+        }
+
+        // The index no longer matches the files' times, which `git status`
+        // would otherwise refresh on disk.
+        let index = work.join(".git/index");
+        assert!(git::unsaved_work(&work).is_empty());
+        let index_mtime = fs::metadata(&index).unwrap().modified().unwrap();
+        assert_eq!(filetime::FileTime::from_system_time(index_mtime), old);
+
+        fs::write(work.join(".git/FETCH_HEAD"), "").unwrap();
+        let newest = walk_tree(&work, &ScanOptions::default(), None, None).unwrap();
+        assert_eq!(filetime::FileTime::from_system_time(newest), old);
+    }
+}
@@ -1,98 +1,930 @@
-use clap::{Parser, Subcommand};
+use anyhow::{Context, bail};
+use clap::{Args, Parser, Subcommand};
+use std::io::IsTerminal;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::time::Duration;
+use sweeper::cache;
+use sweeper::checksum::{self, Algorithm};
+use sweeper::clean;
+use sweeper::compress::Compression;
+use sweeper::config::{self, Settings};
+use sweeper::dupes::{self, Keep};
+use sweeper::filter::{self, Filter};
+use sweeper::kind::ProjectKind;
+use sweeper::manifest;
+use sweeper::output::{self, OutputFormat};
+use sweeper::restore;
+use sweeper::rules::RuleSet;
+use sweeper::tui::{self, Action};
+use sweeper::watch::{self, WatchOptions};
+use sweeper::{ActivityMode, Depth, OrganizeOptions, ScanOptions, ScanReport, SortKey, journal};
 
 #[derive(Parser, Debug)]
 #[command(name = "sweeper", version, about = "Organize files and clean stale projects safely")]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Output format
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// Named profile from the config files ([profiles.NAME])
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    /// Also list what was excluded, and by which rule
+    #[arg(long, short, global = true)]
+    verbose: bool,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
-    /// Organize files in a folder by type
+    /// Organize files in a folder by type, or by rules from the config
     Organize {
         path: PathBuf,
         #[arg(long)]
         dry_run: bool,
+        /// Also classify files by their content (magic bytes), not just the extension
+        #[arg(long)]
+        sniff: bool,
+        /// Only report files whose extension doesn't match their content
+        #[arg(long)]
+        check_types: bool,
+        /// Leave a file in place when an identical copy is already at its target
+        #[arg(long)]
+        skip_identical: bool,
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+
+    /// Keep organizing a folder as new files arrive (until Ctrl+C)
+    Watch {
+        path: PathBuf,
+        /// Seconds a file must stay unchanged before it is moved [default: 5]
+        #[arg(long, value_name = "SECS")]
+        settle: Option<u64>,
+        /// Also classify files by their content (magic bytes), not just the extension
+        #[arg(long)]
+        sniff: bool,
+        /// Leave a file in place when an identical copy is already at its target
+        #[arg(long)]
+        skip_identical: bool,
+        /// Log what would be moved without moving anything
+        #[arg(long)]
+        dry_run: bool,
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+
+    /// Find duplicate files and optionally remove or hardlink the extra copies
+    Dupes {
+        path: PathBuf,
+        /// Keep the newest or oldest copy of each group and trash the rest
+        #[arg(long, value_enum)]
+        keep: Option<Keep>,
+        /// Replace extra copies with hardlinks to the kept one (the copies still go to the system bin)
+        #[arg(long)]
+        hardlink: bool,
+        #[arg(long)]
+        yes: bool,
     },
 
     /// Scan for stale project folders
     Scan {
-        path: PathBuf,
-        #[arg(long, default_value_t = 30)]
-        older_than: u64,
+        /// Folders to scan (default: `roots` from config)
+        #[arg(value_name = "PATH")]
+        paths: Vec<PathBuf>,
+        /// Days without activity before a project is stale [default: 30]
+        #[arg(long)]
+        older_than: Option<u64>,
+        #[command(flatten)]
+        scan: ScanArgs,
+        /// Pick stale projects in a terminal UI, then archive or trash them
+        #[arg(long)]
+        interactive: bool,
+        /// Archive root for projects archived from the UI (default: `archive.dest` from config)
+        #[arg(long, requires = "interactive")]
+        dest: Option<PathBuf>,
+        /// Let the UI pick git checkouts with uncommitted, stashed or unpushed work
+        #[arg(long, requires = "interactive")]
+        force_dirty: bool,
     },
 
     /// Archive stale project folders into YYYY-MM buckets
+    #[command(args_conflicts_with_subcommands = true)]
     Archive {
-        path: PathBuf,
+        #[command(subcommand)]
+        command: Option<ArchiveCommand>,
+        /// Folders to scan (default: `roots` from config)
+        #[arg(value_name = "PATH")]
+        paths: Vec<PathBuf>,
+        /// Archive root (default: `archive.dest` from config)
+        #[arg(long)]
+        dest: Option<PathBuf>,
+        /// Pack each project into a verified tarball instead of moving the folder [default: none]
+        #[arg(long, value_enum)]
+        compress: Option<Compression>,
+        /// Record per-file checksums and check the archived copy against them
+        #[arg(long, value_enum)]
+        checksum: Option<Algorithm>,
+        /// Days without activity before a project is stale [default: 30]
+        #[arg(long)]
+        older_than: Option<u64>,
+        #[command(flatten)]
+        scan: ScanArgs,
+        /// Include git checkouts with uncommitted, stashed or unpushed work
         #[arg(long)]
-        dest: PathBuf,
-        #[arg(long, default_value_t = 30)]
-        older_than: u64,
+        force_dirty: bool,
         #[arg(long)]
         yes: bool,
     },
 
+    /// Move an archived project back to where it was archived from
+    Restore {
+        /// Project name (or part of it) to look for in the archive
+        #[arg(required_unless_present = "from", conflicts_with = "from")]
+        name: Option<String>,
+        /// Exact archived folder, e.g. ARCHIVE/2026-02/foo
+        #[arg(long, value_name = "PATH")]
+        from: Option<PathBuf>,
+        /// Archive root to search (default: `archive.dest` from config)
+        #[arg(long)]
+        dest: Option<PathBuf>,
+        /// Restore into this folder instead of the original location
+        #[arg(long, value_name = "DIR")]
+        to: Option<PathBuf>,
+        #[arg(long)]
+        yes: bool,
+    },
+
+    /// Re-hash archived projects and compare them with their recorded checksums
+    Verify {
+        /// Archive root or a single YYYY-MM bucket
+        archive_dir: PathBuf,
+    },
+
     /// Send stale project folders to system bin (safe delete)
     Delete {
-        path: PathBuf,
-        #[arg(long, default_value_t = 90)]
-        older_than: u64,
+        /// Folders to scan (default: `roots` from config)
+        #[arg(value_name = "PATH")]
+        paths: Vec<PathBuf>,
+        /// Days without activity before a project is stale [default: 90]
+        #[arg(long)]
+        older_than: Option<u64>,
+        #[command(flatten)]
+        scan: ScanArgs,
+        /// Include git checkouts with uncommitted, stashed or unpushed work
+        #[arg(long)]
+        force_dirty: bool,
         #[arg(long)]
         yes: bool,
     },
+
+    /// Remove regenerable build output (target/, node_modules/, ...) from stale projects
+    Clean {
+        /// Folders to scan (default: `roots` from config)
+        #[arg(value_name = "PATH")]
+        paths: Vec<PathBuf>,
+        /// Days without activity before a project is stale [default: 30]
+        #[arg(long)]
+        older_than: Option<u64>,
+        #[command(flatten)]
+        scan: ScanArgs,
+        /// Delete the artifact folders outright instead of moving them to the bin
+        #[arg(long)]
+        permanent: bool,
+        #[arg(long)]
+        yes: bool,
+    },
+
+    /// Manage the scan index kept between runs
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+
+    /// Reverse a previous organize, archive, delete, dupes or clean run (latest by default)
+    Undo { run_id: Option<String> },
+
+    /// Inspect configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ArchiveCommand {
+    /// List what the archive holds, from the manifests in each bucket
+    List {
+        /// Only entries whose name or original path contains this
+        query: Option<String>,
+        /// Archive root (default: `archive.dest` from config)
+        #[arg(long)]
+        dest: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheCommand {
+    /// Drop index entries for projects that no longer exist
+    Prune {
+        /// Drop every entry; the next scan rebuilds the index
+        #[arg(long)]
+        all: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigCommand {
+    /// Print the merged effective config and where each value came from
+    Show {
+        /// Also apply this folder's .sweeper.toml
+        path: Option<PathBuf>,
+    },
+}
+
+// What a command may touch, on top of `.sweeperignore` files.
+#[derive(Args, Debug)]
+struct FilterArgs {
+    /// Only consider entries matching this glob, by name or path (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    include: Vec<String>,
+    /// Leave entries matching this glob alone, by name or path (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+}
+
+impl FilterArgs {
+    fn filter(&self) -> anyhow::Result<Filter> {
+        Filter::new(&self.include, &self.exclude)
+    }
+}
+
+// Options shared by every command that scans for stale projects.
+#[derive(Args, Debug)]
+struct ScanArgs {
+    /// Activity signal: file mtimes, last git commit, any git activity (git-any), or the newer of mtime and commit [default: mtime]
+    #[arg(long, value_enum)]
+    activity: Option<ActivityMode>,
+    /// How deep to look for recent files inside each project: N or 'unlimited' [default: unlimited]
+    #[arg(long)]
+    depth: Option<Depth>,
+    /// Stop walking a project at the first file newer than the threshold [default]
+    #[arg(long, overrides_with = "no_early_exit")]
+    early_exit: bool,
+    /// Walk every project to the end, even once it is known to be fresh
+    #[arg(long, overrides_with = "early_exit")]
+    no_early_exit: bool,
+    /// Extra directory name to skip when measuring activity (repeatable)
+    #[arg(long = "ignore-dir", value_name = "NAME")]
+    ignore_dirs: Vec<String>,
+    /// Don't skip the built-in build/dependency dirs (target, node_modules, ...)
+    #[arg(long)]
+    no_default_ignores: bool,
+    /// Skip sizing stale projects (faster: only walks what activity needs)
+    #[arg(long)]
+    no_size: bool,
+    /// Order stale projects by age or by size [default: age]
+    #[arg(long, value_enum)]
+    sort: Option<SortKey>,
+    /// Only projects of these kinds, e.g. `rust,node`
+    #[arg(long = "kind", value_enum, value_delimiter = ',', value_name = "KIND")]
+    kinds: Vec<ProjectKind>,
+    /// Find projects further down (e.g. ~/code/ORG/REPO), stopping at VCS dirs,
+    /// build manifests and `.sweeper-project` markers
+    #[arg(long)]
+    discover: bool,
+    /// Levels below the root to look for projects; implies --discover [default: 4]
+    #[arg(long, value_name = "N")]
+    discover_depth: Option<NonZeroUsize>,
+    /// Projects to walk in parallel [default: number of cores, at most 8]
+    #[arg(long, short = 'j', value_name = "N")]
+    jobs: Option<NonZeroUsize>,
+    /// Walk every folder again instead of reusing the scan index
+    #[arg(long)]
+    no_cache: bool,
+    #[command(flatten)]
+    filter: FilterArgs,
+}
+
+impl ScanArgs {
+    // Flags win over config; config wins over built-in defaults.
+    fn options(&self, older_than: u64, settings: &Settings) -> anyhow::Result<ScanOptions> {
+        let mut ignore_dirs: Vec<String> = if self.no_default_ignores || !settings.default_ignores {
+            Vec::new()
+        } else {
+            sweeper::DEFAULT_IGNORED_DIRS
+                .iter()
+                .map(|s| s.to_string())
+                .collect()
+        };
+        ignore_dirs.extend(settings.ignore_dirs.iter().cloned());
+        ignore_dirs.extend(self.ignore_dirs.iter().cloned());
+
+        Ok(ScanOptions {
+            older_than_days: older_than,
+            activity: self.activity.unwrap_or(settings.activity),
+            depth: self.depth.unwrap_or(settings.depth),
+            early_exit: !self.no_early_exit && (self.early_exit || settings.early_exit),
+            ignore_dirs,
+            sizes: !self.no_size && settings.sizes,
+            sort: self.sort.unwrap_or(settings.sort),
+            kinds: self.kinds.clone(),
+            discover_depth: if self.discover || self.discover_depth.is_some() || settings.discover {
+                self.discover_depth
+                    .map(NonZeroUsize::get)
+                    .unwrap_or(settings.discover_depth)
+            } else {
+                1
+            },
+            jobs: self
+                .jobs
+                .map(NonZeroUsize::get)
+                .unwrap_or_else(sweeper::default_jobs),
+            cache: !self.no_cache && settings.cache,
+            filter: self.filter.filter()?,
+        })
+    }
+}
+
+// The PATHs given, or else every root listed in the config. Each root is
+// scanned once however often it is listed; overlapping roots are an error.
+fn resolve_roots(paths: Vec<PathBuf>, profile: Option<&str>) -> anyhow::Result<Vec<PathBuf>> {
+    let roots = if paths.is_empty() {
+        config::load(profile, None)?.settings.roots
+    } else {
+        paths
+    };
+    if roots.is_empty() {
+        bail!("No PATH given and no `roots` set in the config");
+    }
+    let (roots, duplicates) = sweeper::distinct_roots(&roots)?;
+    for d in duplicates {
+        eprintln!("Skipping duplicate root: {}", d.display());
+    }
+    Ok(roots)
+}
+
+// Scan every root under its own config and merge the results into one report.
+// Also returns those configs, in root order.
+fn scan_roots(
+    roots: &[PathBuf],
+    profile: Option<&str>,
+    scan: &ScanArgs,
+    older_than: Option<u64>,
+    default_older_than: fn(&Settings) -> u64,
+) -> anyhow::Result<(ScanReport, Vec<Settings>)> {
+    let mut reports = Vec::new();
+    let mut settings = Vec::new();
+    for root in roots {
+        let s = config::load(profile, Some(root))?.settings;
+        let opts = scan.options(older_than.unwrap_or(default_older_than(&s)), &s)?;
+        reports.push(sweeper::scan_projects(root, &opts)?);
+        settings.push(s);
+    }
+    let sort = scan
+        .sort
+        .or(settings.first().map(|s| s.sort))
+        .unwrap_or_default();
+    Ok((ScanReport::merge(reports, sort), settings))
+}
+
+// A setting that one run applies to every root, so their configs must agree.
+fn agreed<T: PartialEq>(
+    settings: &[Settings],
+    key: &str,
+    get: impl Fn(&Settings) -> T,
+) -> anyhow::Result<T> {
+    let mut values = settings.iter().map(get);
+    let first = values.next().context("No roots to scan")?;
+    if values.any(|v| v != first) {
+        bail!(
+            "The roots' configs set different `{}`; pass it as a flag",
+            key
+        );
+    }
+    Ok(first)
+}
+
+// Let the user pick from `report.stale` in the UI, then archive or trash the
+// picks the same way `archive --yes` and `delete --yes` would, holding back
+// unsaved work unless `force_dirty`.
+fn run_interactive(
+    report: &ScanReport,
+    dest: Option<PathBuf>,
+    settings: &[Settings],
+    force_dirty: bool,
+) -> anyhow::Result<()> {
+    if report.stale.is_empty() {
+        sweeper::print_report(report);
+        return Ok(());
+    }
+
+    let Some(selection) = tui::select(report, dest.is_some(), force_dirty)? else {
+        println!("Nothing done.");
+        return Ok(());
+    };
+    let mut picked = ScanReport {
+        stale: selection.items,
+        ..report.clone()
+    };
+    // Checked again: the work may have changed while the UI was open.
+    let held = if force_dirty {
+        Vec::new()
+    } else {
+        sweeper::hold_back_unsaved_work(&mut picked)
+    };
+    sweeper::print_held_back(&held);
+    if picked.stale.is_empty() {
+        println!("Nothing done.");
+        return Ok(());
+    }
+
+    match selection.action {
+        Action::Archive => {
+            let dest = dest.context("No archive destination")?;
+            let plan = sweeper::build_archive_plan(
+                &picked,
+                &dest,
+                agreed(settings, "archive.compress", |s| s.archive.compress)?,
+                agreed(settings, "archive.checksum", |s| s.archive.checksum)?,
+            )?;
+            sweeper::print_plan(&plan);
+            let run = journal::record("archive", |run| sweeper::apply_archive_plan(&plan, run))?;
+            println!(
+                "\nArchived successfully. (undo with: sweeper undo {})",
+                run.id
+            );
+        }
+        Action::Trash => {
+            sweeper::print_report(&picked);
+            let run =
+                journal::record("delete", |run| sweeper::delete_to_trash(&picked.stale, run))?;
+            println!(
+                "\nMoved to system bin successfully. (undo with: sweeper undo {})",
+                run.id
+            );
+        }
+    }
+    Ok(())
 }
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
+    let format = cli.format;
+    let text = format == OutputFormat::Text;
+    let profile = cli.profile.as_deref();
+    let verbose = cli.verbose && text;
+
     match cli.command {
-        Commands::Organize { path, dry_run } => {
-            sweeper::organize_folder(&path, dry_run)?;
+        Commands::Organize {
+            path,
+            dry_run,
+            sniff,
+            check_types,
+            skip_identical,
+            filter,
+        } => {
+            if check_types {
+                let found = sweeper::find_mismatches(&path)?;
+                if text {
+                    sweeper::print_mismatches(&found);
+                }
+                output::mismatch_document(&path, &found).write(format)?;
+                return Ok(());
+            }
+
+            let cfg = config::load(profile, Some(&path))?;
+            let rules = RuleSet::new(&cfg.settings.organize.rules)?;
+            let opts = OrganizeOptions {
+                sniff: sniff || cfg.settings.organize.sniff,
+                skip_identical: skip_identical || cfg.settings.organize.skip_identical,
+            };
+            let (moves, excluded) =
+                sweeper::plan_organize(&path, &rules, &opts, &filter.filter()?)?;
+            if verbose {
+                filter::print_excluded(&excluded);
+            }
+            if text {
+                sweeper::print_organize(&moves);
+            }
+
+            let mut run_id = None;
+            if !dry_run {
+                let run = journal::record("organize", |run| sweeper::apply_organize(&moves, run))?;
+                if text && !run.is_empty() {
+                    println!("\nJournal run: {}", run.id);
+                }
+                run_id = Some(run.id);
+            } else if text {
+                println!("\nDry-run only. Use without --dry-run to apply.");
+            }
+
+            output::organize_document(&path, &moves, run_id.as_deref())
+                .excluded(&excluded)
+                .write(format)?;
         }
-        Commands::Scan { path, older_than } => {
-            let report = sweeper::scan_projects(&path, older_than)?;
-            sweeper::print_report(&report);
+        Commands::Watch {
+            path,
+            settle,
+            sniff,
+            skip_identical,
+            dry_run,
+            filter,
+        } => {
+            let cfg = config::load(profile, Some(&path))?;
+            let rules = RuleSet::new(&cfg.settings.organize.rules)?;
+            let opts = WatchOptions {
+                settle: Duration::from_secs(settle.unwrap_or(cfg.settings.watch.settle_secs)),
+                organize: OrganizeOptions {
+                    sniff: sniff || cfg.settings.organize.sniff,
+                    skip_identical: skip_identical || cfg.settings.organize.skip_identical,
+                },
+                filter: filter.filter()?,
+                dry_run,
+                format,
+            };
+            watch::watch(&path, &rules, &opts)?;
         }
-        Commands::Archive {
+        Commands::Dupes {
             path,
+            keep,
+            hardlink,
+            yes,
+        } => {
+            let found = dupes::find_dupes(&path)?;
+            let groups = &found.groups;
+            // Hardlinking still needs to know which copy the others point to.
+            let keep = if hardlink {
+                Some(keep.unwrap_or(Keep::Oldest))
+            } else {
+                keep
+            };
+            if text {
+                dupes::print_dupes(&found, keep);
+            }
+
+            let mut run_id = None;
+            let mut applied = false;
+            if let Some(policy) = keep
+                && !groups.is_empty()
+            {
+                if !yes {
+                    if text {
+                        println!("\nDry-run only. Use --yes to apply.");
+                    }
+                } else if hardlink {
+                    let mut skipped = Vec::new();
+                    let run = journal::record("dupes", |run| {
+                        skipped = dupes::hardlink_dupes(groups, policy, run)?;
+                        Ok(())
+                    })?;
+                    applied = true;
+                    if text {
+                        println!(
+                            "\nReplaced extra copies with hardlinks and moved the copies to system bin. (undo with: sweeper undo {})",
+                            run.id
+                        );
+                        for s in &skipped {
+                            println!("  - skipped {}", s);
+                        }
+                    }
+                    run_id = Some(run.id);
+                } else {
+                    let mut skipped = Vec::new();
+                    let run = journal::record("dupes", |run| {
+                        skipped = dupes::trash_dupes(groups, policy, run)?;
+                        Ok(())
+                    })?;
+                    applied = true;
+                    if text {
+                        println!(
+                            "\nMoved extra copies to system bin. (undo with: sweeper undo {})",
+                            run.id
+                        );
+                        for s in &skipped {
+                            println!("  - skipped {}", s);
+                        }
+                    }
+                    run_id = Some(run.id);
+                }
+            }
+
+            let action = match (keep, hardlink) {
+                (None, _) => None,
+                (Some(_), true) => Some("hardlink"),
+                (Some(_), false) => Some("trash"),
+            };
+            output::dupes_document(&path, &found, keep, action, applied, run_id.as_deref())
+                .write(format)?;
+        }
+        Commands::Scan {
+            paths,
+            older_than,
+            scan,
+            interactive,
+            dest,
+            force_dirty,
+        } => {
+            if interactive && (!text || !std::io::stdout().is_terminal()) {
+                bail!("--interactive needs a terminal and text output");
+            }
+            let roots = resolve_roots(paths, profile)?;
+            let (report, settings) =
+                scan_roots(&roots, profile, &scan, older_than, |s| s.scan.older_than)?;
+            if interactive {
+                let dest = match dest {
+                    Some(dest) => Some(dest),
+                    None => agreed(&settings, "archive.dest", |s| s.archive.dest.clone())?,
+                };
+                return run_interactive(&report, dest, &settings, force_dirty);
+            }
+            if verbose {
+                filter::print_excluded(&report.excluded);
+            }
+            if text {
+                sweeper::print_report(&report);
+            }
+            output::scan_document(&report, &[]).write(format)?;
+        }
+        Commands::Archive {
+            command: Some(ArchiveCommand::List { query, dest }),
+            ..
+        } => {
+            let settings = config::load(profile, None)?.settings;
+            let dest = dest
+                .or(settings.archive.dest)
+                .context("No --dest given and no `archive.dest` set in the config")?;
+            let listed = manifest::list(&dest, query.as_deref())?;
+            if text {
+                manifest::print_list(&listed);
+            }
+            output::archive_list_document(&dest, &listed).write(format)?;
+        }
+        Commands::Archive {
+            command: None,
+            paths,
             dest,
+            compress,
+            checksum,
             older_than,
+            scan,
+            force_dirty,
             yes,
         } => {
-            let report = sweeper::scan_projects(&path, older_than)?;
-            let plan = sweeper::build_archive_plan(&report, &dest)?;
-            sweeper::print_plan(&plan);
+            let roots = resolve_roots(paths, profile)?;
+            let (mut report, settings) =
+                scan_roots(&roots, profile, &scan, older_than, |s| s.archive.older_than)?;
+            let dest = match dest {
+                Some(dest) => Some(dest),
+                None => agreed(&settings, "archive.dest", |s| s.archive.dest.clone())?,
+            }
+            .context("No --dest given and no `archive.dest` set in the config")?;
+            let held = if force_dirty {
+                Vec::new()
+            } else {
+                sweeper::hold_back_unsaved_work(&mut report)
+            };
+            let compression = match compress {
+                Some(c) => c,
+                None => agreed(&settings, "archive.compress", |s| s.archive.compress)?,
+            };
+            let checksum = match checksum {
+                Some(a) => Some(a),
+                None => agreed(&settings, "archive.checksum", |s| s.archive.checksum)?,
+            };
+            let plan = sweeper::build_archive_plan(&report, &dest, compression, checksum)?;
+            if verbose {
+                filter::print_excluded(&report.excluded);
+            }
+            if text {
+                sweeper::print_held_back(&held);
+                sweeper::print_plan(&plan);
+            }
 
+            let mut run_id = None;
             if yes {
-                sweeper::apply_archive_plan(&plan)?;
-                println!("\nArchived successfully.");
-            } else {
+                let run =
+                    journal::record("archive", |run| sweeper::apply_archive_plan(&plan, run))?;
+                if text {
+                    println!(
+                        "\nArchived successfully. (undo with: sweeper undo {})",
+                        run.id
+                    );
+                }
+                run_id = Some(run.id);
+            } else if text {
+                println!("\nDry-run only. Use --yes to apply.");
+            }
+
+            output::archive_document(&report, &plan, &held, run_id.as_deref()).write(format)?;
+        }
+        Commands::Restore {
+            name,
+            from,
+            dest,
+            to,
+            yes,
+        } => {
+            let item = match (from, name) {
+                (Some(from), _) => restore::archived_at(&from)?,
+                (None, Some(name)) => {
+                    let settings = config::load(profile, None)?.settings;
+                    let dest = dest
+                        .or(settings.archive.dest)
+                        .context("No --dest given and no `archive.dest` set in the config")?;
+                    let mut matches = restore::find(restore::list_archived(&dest)?, &name);
+                    match matches.len() {
+                        0 => bail!("Nothing matching '{}' in {}", name, dest.display()),
+                        1 => matches.remove(0),
+                        n => {
+                            if text {
+                                println!("{} archived projects match '{}':", n, name);
+                                restore::print_candidates(&matches);
+                            }
+                            bail!("'{}' is ambiguous; pick one with --from PATH", name);
+                        }
+                    }
+                }
+                (None, None) => unreachable!("clap requires NAME or --from"),
+            };
+
+            let plan = restore::plan_restore(&item, to.as_deref())?;
+            if text {
+                restore::print_restore(&plan);
+            }
+
+            let mut run_id = None;
+            if yes {
+                let run = journal::record("restore", |run| restore::apply_restore(&plan, run))?;
+                if text {
+                    println!("\nRestored. (undo with: sweeper undo {})", run.id);
+                }
+                run_id = Some(run.id);
+            } else if text {
                 println!("\nDry-run only. Use --yes to apply.");
             }
+
+            output::restore_document(&plan, run_id.as_deref()).write(format)?;
+        }
+        Commands::Verify { archive_dir } => {
+            let verified = checksum::verify_archive(&archive_dir)?;
+            if text {
+                checksum::print_verify(&verified);
+            }
+            output::verify_document(&archive_dir, &verified).write(format)?;
+
+            let failed = verified.iter().filter(|v| v.failed()).count();
+            if failed > 0 {
+                bail!("{} archived project(s) failed verification", failed);
+            }
         }
         Commands::Delete {
-            path,
+            paths,
             older_than,
+            scan,
+            force_dirty,
             yes,
         } => {
-            let report = sweeper::scan_projects(&path, older_than)?;
+            let roots = resolve_roots(paths, profile)?;
+            let (mut report, _) =
+                scan_roots(&roots, profile, &scan, older_than, |s| s.delete.older_than)?;
+            let held = if force_dirty {
+                Vec::new()
+            } else {
+                sweeper::hold_back_unsaved_work(&mut report)
+            };
+            if verbose {
+                filter::print_excluded(&report.excluded);
+            }
+            if text {
+                sweeper::print_held_back(&held);
+            }
 
+            let mut run_id = None;
             if report.stale.is_empty() {
-                println!("Nothing to delete.");
+                if text {
+                    println!("Nothing to delete.");
+                }
+            } else {
+                if text {
+                    sweeper::print_report(&report);
+                }
+
+                if yes {
+                    let run = journal::record("delete", |run| {
+                        sweeper::delete_to_trash(&report.stale, run)
+                    })?;
+                    if text {
+                        println!(
+                            "\nMoved to system bin successfully. (undo with: sweeper undo {})",
+                            run.id
+                        );
+                    }
+                    run_id = Some(run.id);
+                } else if text {
+                    println!("\nDry-run only. Use --yes to move to bin.");
+                }
+            }
+
+            output::delete_document(&report, &held, run_id.as_deref()).write(format)?;
+        }
+        Commands::Clean {
+            paths,
+            older_than,
+            scan,
+            permanent,
+            yes,
+        } => {
+            let roots = resolve_roots(paths, profile)?;
+            let (report, _) =
+                scan_roots(&roots, profile, &scan, older_than, |s| s.clean.older_than)?;
+            let items = clean::plan_clean(&report);
+            if verbose {
+                filter::print_excluded(&report.excluded);
+            }
+            if text {
+                clean::print_clean(&items);
+            }
+
+            let mut applied = false;
+            let mut run_id = None;
+            if yes && !items.is_empty() {
+                let run = journal::record("clean", |run| {
+                    clean::apply_clean(&items, permanent, run)
+                })?;
+                applied = true;
+                if text {
+                    let total: u64 = items.iter().map(clean::CleanItem::reclaimable).sum();
+                    if permanent {
+                        println!("\nRemoved. {} reclaimed.", sweeper::fmt_bytes(total));
+                    } else {
+                        println!(
+                            "\nMoved to system bin, {} reclaimable once emptied. (undo with: sweeper undo {})",
+                            sweeper::fmt_bytes(total),
+                            run.id
+                        );
+                    }
+                }
+                // Nothing to undo after a permanent delete.
+                run_id = (!permanent).then_some(run.id);
+            } else if text && !items.is_empty() {
+                if permanent {
+                    println!("\nDry-run only. Use --yes to delete these folders for good.");
+                } else {
+                    println!("\nDry-run only. Use --yes to move these folders to the bin.");
+                }
+            }
+
+            output::clean_document(&report, &items, applied, permanent, run_id.as_deref())
+                .write(format)?;
+        }
+        Commands::Cache {
+            command: CacheCommand::Prune { all },
+        } => {
+            let pruned = cache::prune(all)?;
+            if text {
+                cache::print_prune(&pruned);
+            }
+            output::cache_prune_document(&cache::index_dir()?, &pruned).write(format)?;
+        }
+        Commands::Undo { run_id } => {
+            let report = journal::undo(run_id.as_deref())?;
+            output::undo_document(&report).write(format)?;
+            if !text {
                 return Ok(());
             }
 
-            sweeper::print_report(&report);
+            println!(
+                "Undid run {}: {} moved back, {} restored from bin.",
+                report.undone_run_id,
+                report.run.moves.len() + report.run.packed.len() + report.run.unpacked.len(),
+                report.run.restored.len()
+            );
 
-            if yes {
-                sweeper::delete_to_trash(&report.stale)?;
-                println!("\nMoved to system bin successfully.");
-            } else {
-                println!("\nDry-run only. Use --yes to move to bin.");
+            if !report.conflicts.is_empty() {
+                println!("\nConflicts (left untouched):");
+                for c in &report.conflicts {
+                    println!("  - {}", c);
+                }
+                println!(
+                    "\nOnce resolved, run `sweeper undo {}` again to put back the rest.",
+                    report.undone_run_id
+                );
+            }
+        }
+        Commands::Config {
+            command: ConfigCommand::Show { path },
+        } => {
+            let cfg = config::load(profile, path.as_deref())?;
+            if text {
+                config::print_config(&cfg);
             }
+            output::config_document(&cfg).write(format)?;
         }
     }
 
@@ -0,0 +1,262 @@
+use crate::ProjectItem;
+use crate::checksum::Algorithm;
+use crate::compress::Compression;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Every month bucket keeps a manifest of what was archived into it, from where
+// and why, so projects can find their way back and the archive can be queried
+// without walking it.
+
+pub const MANIFEST_FILE: &str = ".sweeper-manifest.json";
+
+// Bump when a field is renamed, removed or changes meaning.
+pub const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(default = "current_version")]
+    pub schema_version: u32,
+    pub entries: Vec<ManifestEntry>,
+}
+
+fn current_version() -> u32 {
+    MANIFEST_VERSION
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Manifest {
+            schema_version: MANIFEST_VERSION,
+            entries: Vec::new(),
+        }
+    }
+}
+
+// Everything but `name`, `original_path` and `archived_at` is optional so older
+// manifests still load.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    // Folder name inside the bucket (may differ from the original name after a
+    // collision).
+    pub name: String,
+    pub original_path: PathBuf,
+    pub archived_at: DateTime<Local>,
+    #[serde(default)]
+    pub last_activity: Option<DateTime<Local>>,
+    #[serde(default)]
+    pub activity_source: Option<String>,
+    #[serde(default)]
+    pub apparent_bytes: Option<u64>,
+    #[serde(default)]
+    pub disk_bytes: Option<u64>,
+    #[serde(default)]
+    pub file_count: Option<u64>,
+    // How the project is stored: a plain folder, or a tarball of this kind.
+    #[serde(default)]
+    pub compression: Compression,
+    // Size of the tarball, when packed.
+    #[serde(default)]
+    pub archived_bytes: Option<u64>,
+    // Set when per-file checksums were recorded for `sweeper verify`.
+    #[serde(default)]
+    pub checksum: Option<Algorithm>,
+    #[serde(default)]
+    pub host: Option<String>,
+    #[serde(default)]
+    pub sweeper_version: Option<String>,
+}
+
+impl ManifestEntry {
+    pub fn new(
+        name: &str,
+        item: &ProjectItem,
+        compression: Compression,
+        checksum: Option<Algorithm>,
+    ) -> Self {
+        ManifestEntry {
+            name: name.to_string(),
+            original_path: std::path::absolute(&item.path).unwrap_or_else(|_| item.path.clone()),
+            archived_at: Local::now(),
+            last_activity: Some(item.last_modified.into()),
+            activity_source: Some(item.activity_source.label().to_string()),
+            apparent_bytes: item.size.map(|s| s.apparent_bytes),
+            disk_bytes: item.size.map(|s| s.disk_bytes),
+            file_count: item.size.map(|s| s.file_count),
+            compression,
+            archived_bytes: None,
+            checksum,
+            host: Some(gethostname::gethostname().to_string_lossy().to_string()),
+            sweeper_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        }
+    }
+}
+
+impl Manifest {
+    pub fn load(bucket_dir: &Path) -> Result<Self> {
+        let path = bucket_dir.join(MANIFEST_FILE);
+        let text = match fs::read_to_string(&path) {
+            Ok(t) => t,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Manifest::default()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read: {}", path.display()));
+            }
+        };
+        serde_json::from_str(&text).with_context(|| format!("Invalid manifest: {}", path.display()))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ManifestEntry> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+
+    // A folder name can be reused once its previous occupant was restored, so
+    // the newest entry for a name replaces the old one.
+    pub fn upsert(&mut self, entry: ManifestEntry) {
+        self.entries.retain(|e| e.name != entry.name);
+        self.entries.push(entry);
+    }
+
+    // Written to a temp file and renamed into place, so a crash never leaves a
+    // half-written manifest behind.
+    pub fn save(&self, bucket_dir: &Path) -> Result<()> {
+        let path = bucket_dir.join(MANIFEST_FILE);
+        let tmp = bucket_dir.join(format!("{}.tmp", MANIFEST_FILE));
+        let text = serde_json::to_string_pretty(self)?;
+        fs::write(&tmp, text + "\n")
+            .with_context(|| format!("Failed to write: {}", tmp.display()))?;
+        fs::rename(&tmp, &path).with_context(|| format!("Failed to write: {}", path.display()))
+    }
+}
+
+// Add one entry to the manifest of the bucket `archived` now lives in.
+pub fn record(
+    archived: &Path,
+    item: &ProjectItem,
+    compression: Compression,
+    checksum: Option<Algorithm>,
+) -> Result<()> {
+    let (Some(bucket_dir), Some(name)) = (archived.parent(), archived.file_name()) else {
+        return Ok(());
+    };
+    let mut entry = ManifestEntry::new(&name.to_string_lossy(), item, compression, checksum);
+    if compression != Compression::None {
+        entry.archived_bytes = fs::metadata(archived).ok().map(|m| m.len());
+    }
+    let mut manifest = Manifest::load(bucket_dir)?;
+    manifest.upsert(entry);
+    manifest.save(bucket_dir)
+}
+
+pub fn is_bucket_name(name: &str) -> bool {
+    let b = name.as_bytes();
+    b.len() == 7
+        && b[4] == b'-'
+        && b.iter()
+            .enumerate()
+            .all(|(i, c)| i == 4 || c.is_ascii_digit())
+}
+
+// The `YYYY-MM` bucket folders under an archive root, oldest first.
+pub fn buckets(dest_root: &Path) -> Result<Vec<PathBuf>> {
+    let mut buckets: Vec<PathBuf> = fs::read_dir(dest_root)
+        .with_context(|| format!("Cannot read archive: {}", dest_root.display()))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(is_bucket_name)
+        })
+        .collect();
+    buckets.sort();
+    Ok(buckets)
+}
+
+#[derive(Debug, Clone)]
+pub struct Listed {
+    pub bucket: String,
+    pub path: PathBuf,
+    // False once the project was restored or removed by hand.
+    pub present: bool,
+    pub entry: ManifestEntry,
+}
+
+// Every manifest entry under `dest_root`, oldest bucket first, optionally only
+// those whose name or original path contains `query` (case-insensitive).
+pub fn list(dest_root: &Path, query: Option<&str>) -> Result<Vec<Listed>> {
+    let query = query.map(str::to_lowercase);
+    let mut listed = Vec::new();
+
+    for bucket_dir in buckets(dest_root)? {
+        let bucket = bucket_dir
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut entries = Manifest::load(&bucket_dir)?.entries;
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        for entry in entries {
+            if let Some(q) = &query {
+                let haystack = format!("{}\n{}", entry.name, entry.original_path.display());
+                if !haystack.to_lowercase().contains(q) {
+                    continue;
+                }
+            }
+            let path = bucket_dir.join(&entry.name);
+            listed.push(Listed {
+                bucket: bucket.clone(),
+                present: path.exists(),
+                path,
+                entry,
+            });
+        }
+    }
+    Ok(listed)
+}
+
+pub fn print_list(listed: &[Listed]) {
+    if listed.is_empty() {
+        println!("Nothing archived.");
+        return;
+    }
+
+    let mut current = "";
+    for l in listed {
+        if l.bucket != current {
+            current = &l.bucket;
+            println!("{}:", current);
+        }
+        let e = &l.entry;
+        let mut size = e
+            .disk_bytes
+            .map(crate::fmt_bytes)
+            .unwrap_or_else(|| "?".to_string());
+        if let Some(packed) = e.archived_bytes {
+            size = format!("{} -> {}", size, crate::fmt_bytes(packed));
+        }
+        let last = e
+            .last_activity
+            .map(|t| t.format("%Y-%m-%d").to_string())
+            .unwrap_or_else(|| "?".to_string());
+        let gone = if l.present { "" } else { "  [no longer here]" };
+        println!(
+            "  {:<24} {:>10}  last active {}  from {}{}",
+            e.name,
+            size,
+            last,
+            e.original_path.display(),
+            gone
+        );
+        println!(
+            "  {:<24} archived {} on {} by sweeper {}",
+            "",
+            e.archived_at.format("%Y-%m-%d %H:%M"),
+            e.host.as_deref().unwrap_or("?"),
+            e.sweeper_version.as_deref().unwrap_or("?")
+        );
+    }
+}
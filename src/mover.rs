@@ -0,0 +1,460 @@
+use crate::checksum::{self, TreeSums};
+use crate::dupes;
+use anyhow::{Context, Result, bail};
+use filetime::FileTime;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+// Move a file or directory tree. Tries a plain rename first and falls back to
+// copy + verify + delete when source and destination are on different filesystems.
+// Whatever is at `to` is never replaced: the move fails instead. With `expected`
+// the copy must also match those checksums before the source goes; a rename
+// keeps the very same files and needs no such check.
+pub fn move_path(from: &Path, to: &Path, expected: Option<&TreeSums>) -> Result<()> {
+    match rename_new(from, to) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => copy_then_remove(from, to, expected),
+        Err(e) => Err(e)
+            .with_context(|| format!("Failed to move '{}' -> '{}'", from.display(), to.display())),
+    }
+}
+
+fn copy_then_remove(from: &Path, to: &Path, expected: Option<&TreeSums>) -> Result<()> {
+    // Never copy over something that already exists: on failure we clean up `to`,
+    // and that must only ever remove what we created ourselves.
+    if fs::symlink_metadata(to).is_ok() {
+        bail!("Destination already exists: {}", to.display());
+    }
+
+    let copied = copy_tree(from, to)
+        .and_then(|_| verify_copy(from, to))
+        .and_then(|_| match expected {
+            Some(sums) => checksum::ensure_matches(sums, to),
+            None => Ok(()),
+        });
+    if let Err(e) = copied {
+        let failed = format!(
+            "Failed to copy '{}' -> '{}' across filesystems",
+            from.display(),
+            to.display()
+        );
+        // Nothing to clean up when the copy failed before creating anything.
+        return Err(match remove_path(to) {
+            Ok(()) => e.context(failed),
+            Err(cleanup) if cleanup.kind() == io::ErrorKind::NotFound => e.context(failed),
+            Err(cleanup) => e.context(format!(
+                "{}, and failed to remove the partial copy: {}",
+                failed, cleanup
+            )),
+        });
+    }
+
+    remove_path(from).with_context(|| {
+        format!(
+            "Copied to '{}' but failed to remove source '{}'",
+            to.display(),
+            from.display()
+        )
+    })
+}
+
+fn copy_tree(from: &Path, to: &Path) -> Result<()> {
+    // Directories get their permissions and mtimes applied last (deepest first),
+    // otherwise writing their children would bump the mtime again and a read-only
+    // dir would refuse the children altogether.
+    let mut dirs: Vec<(PathBuf, fs::Metadata)> = Vec::new();
+
+    for entry in WalkDir::new(from).follow_links(false) {
+        let entry = entry.with_context(|| format!("Failed to walk: {}", from.display()))?;
+        let rel = entry.path().strip_prefix(from)?;
+        let target = if rel.as_os_str().is_empty() {
+            to.to_path_buf()
+        } else {
+            to.join(rel)
+        };
+        let meta = entry
+            .metadata()
+            .with_context(|| format!("Failed to stat: {}", entry.path().display()))?;
+        let ft = meta.file_type();
+
+        if ft.is_dir() {
+            fs::create_dir(&target)
+                .with_context(|| format!("Failed to create dir: {}", target.display()))?;
+            copy_xattrs(entry.path(), &target)?;
+            dirs.push((target, meta));
+        } else if ft.is_symlink() {
+            let link = fs::read_link(entry.path())
+                .with_context(|| format!("Failed to read link: {}", entry.path().display()))?;
+            create_symlink(&link, &target, entry.path())
+                .with_context(|| format!("Failed to create symlink: {}", target.display()))?;
+            copy_xattrs(entry.path(), &target)?;
+            let mtime = FileTime::from_last_modification_time(&meta);
+            let atime = FileTime::from_last_access_time(&meta);
+            filetime::set_symlink_file_times(&target, atime, mtime)
+                .with_context(|| format!("Failed to set times: {}", target.display()))?;
+        } else if ft.is_file() {
+            copy_file(entry.path(), &target, &meta).with_context(|| {
+                format!(
+                    "Failed to copy '{}' -> '{}'",
+                    entry.path().display(),
+                    target.display()
+                )
+            })?;
+            copy_xattrs(entry.path(), &target)?;
+            set_times(&target, &meta)?;
+        } else {
+            bail!("Unsupported file type: {}", entry.path().display());
+        }
+    }
+
+    for (dir, meta) in dirs.iter().rev() {
+        fs::set_permissions(dir, meta.permissions())
+            .with_context(|| format!("Failed to set permissions: {}", dir.display()))?;
+        set_times(dir, meta)?;
+    }
+
+    Ok(())
+}
+
+// Like `fs::copy`, but refuses to write into a file that already exists.
+fn copy_file(from: &Path, to: &Path, meta: &fs::Metadata) -> io::Result<()> {
+    let mut src = File::open(from)?;
+    let mut dst = OpenOptions::new().write(true).create_new(true).open(to)?;
+    io::copy(&mut src, &mut dst)?;
+    fs::set_permissions(to, meta.permissions())
+}
+
+fn verify_copy(from: &Path, to: &Path) -> Result<()> {
+    let mut count = 0usize;
+
+    for entry in WalkDir::new(from).follow_links(false) {
+        let entry = entry?;
+        let rel = entry.path().strip_prefix(from)?;
+        let target = to.join(rel);
+        let src = entry.metadata()?;
+        let dst = fs::symlink_metadata(&target)
+            .with_context(|| format!("Missing after copy: {}", target.display()))?;
+        count += 1;
+
+        if src.file_type().is_dir() != dst.file_type().is_dir()
+            || src.file_type().is_symlink() != dst.file_type().is_symlink()
+        {
+            bail!("File type mismatch after copy: {}", target.display());
+        }
+        if src.file_type().is_symlink() {
+            if fs::read_link(entry.path())? != fs::read_link(&target)? {
+                bail!("Symlink target mismatch after copy: {}", target.display());
+            }
+        } else if src.is_file() {
+            if src.len() != dst.len() {
+                bail!(
+                    "Size mismatch after copy: {} ({} vs {} bytes)",
+                    target.display(),
+                    src.len(),
+                    dst.len()
+                );
+            }
+            // The source is about to be deleted, so every byte has to match.
+            if !dupes::same_content(entry.path(), &target)
+                .with_context(|| format!("Failed to compare: {}", target.display()))?
+            {
+                bail!("Content mismatch after copy: {}", target.display());
+            }
+        }
+    }
+
+    let copied = WalkDir::new(to).follow_links(false).into_iter().count();
+    if copied != count {
+        bail!(
+            "Entry count mismatch after copy: {} (expected {}, found {})",
+            to.display(),
+            count,
+            copied
+        );
+    }
+
+    Ok(())
+}
+
+fn set_times(path: &Path, meta: &fs::Metadata) -> Result<()> {
+    let mtime = FileTime::from_last_modification_time(meta);
+    let atime = FileTime::from_last_access_time(meta);
+    filetime::set_file_times(path, atime, mtime)
+        .with_context(|| format!("Failed to set times: {}", path.display()))
+}
+
+// `fs::rename` that fails with `AlreadyExists` instead of replacing whatever is
+// at `to`, even when something appears there between a check and the rename.
+#[cfg(target_os = "linux")]
+pub(crate) fn rename_new(from: &Path, to: &Path) -> io::Result<()> {
+    let (from_c, to_c) = (c_path(from)?, c_path(to)?);
+    let rc = unsafe {
+        libc::renameat2(
+            libc::AT_FDCWD,
+            from_c.as_ptr(),
+            libc::AT_FDCWD,
+            to_c.as_ptr(),
+            libc::RENAME_NOREPLACE,
+        )
+    };
+    if rc == 0 {
+        return Ok(());
+    }
+    match io::Error::last_os_error() {
+        // Filesystems without RENAME_NOREPLACE (some network and FUSE mounts).
+        e if matches!(e.raw_os_error(), Some(libc::EINVAL | libc::ENOSYS)) => {
+            rename_checked(from, to)
+        }
+        e => Err(e),
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn rename_new(from: &Path, to: &Path) -> io::Result<()> {
+    let (from_c, to_c) = (c_path(from)?, c_path(to)?);
+    if unsafe { libc::renamex_np(from_c.as_ptr(), to_c.as_ptr(), libc::RENAME_EXCL) } == 0 {
+        return Ok(());
+    }
+    match io::Error::last_os_error() {
+        e if e.raw_os_error() == Some(libc::ENOTSUP) => rename_checked(from, to),
+        e => Err(e),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub(crate) fn rename_new(from: &Path, to: &Path) -> io::Result<()> {
+    rename_checked(from, to)
+}
+
+// Best effort where the OS has no exclusive rename: check, then rename.
+fn rename_checked(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::symlink_metadata(to).is_ok() {
+        return Err(io::Error::from(io::ErrorKind::AlreadyExists));
+    }
+    fs::rename(from, to)
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn c_path(path: &Path) -> io::Result<std::ffi::CString> {
+    use std::os::unix::ffi::OsStrExt;
+    std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))
+}
+
+pub(crate) fn remove_path(path: &Path) -> io::Result<()> {
+    let meta = fs::symlink_metadata(path)?;
+    if meta.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+#[cfg(unix)]
+fn create_symlink(link: &Path, target: &Path, _src: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(link, target)
+}
+
+#[cfg(windows)]
+fn create_symlink(link: &Path, target: &Path, src: &Path) -> io::Result<()> {
+    // Windows needs to know whether the link points at a directory.
+    if fs::metadata(src).map(|m| m.is_dir()).unwrap_or(false) {
+        std::os::windows::fs::symlink_dir(link, target)
+    } else {
+        std::os::windows::fs::symlink_file(link, target)
+    }
+}
+
+#[cfg(unix)]
+fn copy_xattrs(from: &Path, to: &Path) -> Result<()> {
+    let names = match xattr::list(from) {
+        Ok(names) => names,
+        Err(e) if xattrs_unsupported(&e) => return Ok(()),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to list xattrs: {}", from.display()));
+        }
+    };
+
+    // One the destination won't take (a `security.*` or `trusted.*` name
+    // without the privilege, a value too big for it) is left behind with a
+    // warning rather than failing the whole move.
+    for name in names {
+        let set = match xattr::get(from, &name) {
+            Ok(Some(value)) => xattr::set(to, &name, &value),
+            Ok(None) => continue,
+            Err(e) => Err(e),
+        };
+        match set {
+            Ok(()) => {}
+            // Destination filesystem without xattr support: nothing we can keep.
+            Err(e) if xattrs_unsupported(&e) => return Ok(()),
+            Err(e) => eprintln!(
+                "Warning: failed to copy xattr '{}' to {}: {}",
+                name.to_string_lossy(),
+                to.display(),
+                e
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn xattrs_unsupported(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::Unsupported || e.raw_os_error() == Some(libc::ENOTSUP)
+}
+
+#[cfg(not(unix))]
+fn copy_xattrs(_from: &Path, _to: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn copy_then_remove_keeps_contents_links_and_times() {
+        let tmp = tempfile::tempdir().unwrap();
+        let from = tmp.path().join("project");
+        write(&from.join("src/main.rs"), "fn main() {}\n");
+        write(&from.join("README"), "hello\n");
+        let old = FileTime::from_unix_time(1_600_000_000, 0);
+        filetime::set_file_mtime(from.join("README"), old).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("README", from.join("link")).unwrap();
+
+        let to = tmp.path().join("archive/project");
+        fs::create_dir_all(to.parent().unwrap()).unwrap();
+        copy_then_remove(&from, &to, None).unwrap();
+
+        assert!(!from.exists());
+        assert_eq!(
+            fs::read_to_string(to.join("src/main.rs")).unwrap(),
+            "fn main() {}\n"
+        );
+        let meta = fs::metadata(to.join("README")).unwrap();
+        assert_eq!(FileTime::from_last_modification_time(&meta), old);
+        #[cfg(unix)]
+        assert_eq!(fs::read_link(to.join("link")).unwrap(), Path::new("README"));
+    }
+
+    #[test]
+    fn verify_copy_catches_same_size_corruption() {
+        let tmp = tempfile::tempdir().unwrap();
+        let from = tmp.path().join("a");
+        let to = tmp.path().join("b");
+        write(&from.join("data.bin"), "0123456789");
+        copy_tree(&from, &to).unwrap();
+        verify_copy(&from, &to).unwrap();
+
+        fs::write(to.join("data.bin"), "0123456780").unwrap();
+        let err = verify_copy(&from, &to).unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("Content mismatch"),
+            "{:#}",
+            err
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failed_copy_keeps_source_and_removes_partial_copy() {
+        let tmp = tempfile::tempdir().unwrap();
+        let from = tmp.path().join("project");
+        write(&from.join("a.txt"), "keep me\n");
+        // A FIFO can't be copied, so the copy fails halfway.
+        let fifo =
+            std::ffi::CString::new(from.join("pipe").as_os_str().as_encoded_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o644) }, 0);
+
+        let to = tmp.path().join("copy");
+        assert!(copy_then_remove(&from, &to, None).is_err());
+        assert!(!to.exists());
+        assert_eq!(fs::read_to_string(from.join("a.txt")).unwrap(), "keep me\n");
+    }
+
+    #[test]
+    fn copy_into_a_missing_folder_reports_only_the_copy_error() {
+        let tmp = tempfile::tempdir().unwrap();
+        let from = tmp.path().join("project");
+        write(&from.join("a.txt"), "keep me\n");
+
+        let err = copy_then_remove(&from, &tmp.path().join("gone/project"), None).unwrap_err();
+        let err = format!("{:#}", err);
+        assert!(!err.contains("partial copy"), "{}", err);
+        assert!(from.join("a.txt").exists());
+    }
+
+    #[test]
+    fn copy_never_overwrites_the_destination() {
+        let tmp = tempfile::tempdir().unwrap();
+        let from = tmp.path().join("project");
+        let to = tmp.path().join("taken");
+        write(&from.join("a.txt"), "source\n");
+        write(&to.join("b.txt"), "already here\n");
+
+        assert!(copy_then_remove(&from, &to, None).is_err());
+        assert_eq!(fs::read_to_string(from.join("a.txt")).unwrap(), "source\n");
+        assert_eq!(
+            fs::read_to_string(to.join("b.txt")).unwrap(),
+            "already here\n"
+        );
+    }
+
+    #[test]
+    fn checksum_mismatch_keeps_source_and_removes_copy() {
+        let tmp = tempfile::tempdir().unwrap();
+        let from = tmp.path().join("project");
+        write(&from.join("data.txt"), "as archived\n");
+        let mut sums = checksum::hash_tree(&from, checksum::Algorithm::Blake3).unwrap();
+        // As if the file had changed between hashing and copying.
+        sums.files.get_mut("data.txt").unwrap().hash = "0".repeat(64);
+
+        let to = tmp.path().join("archive/project");
+        fs::create_dir_all(to.parent().unwrap()).unwrap();
+        let err = copy_then_remove(&from, &to, Some(&sums)).unwrap_err();
+        assert!(format!("{:#}", err).contains("checksums"), "{:#}", err);
+        assert!(!to.exists());
+        assert_eq!(
+            fs::read_to_string(from.join("data.txt")).unwrap(),
+            "as archived\n"
+        );
+
+        let sums = checksum::hash_tree(&from, checksum::Algorithm::Sha256).unwrap();
+        copy_then_remove(&from, &to, Some(&sums)).unwrap();
+        assert!(!from.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn xattrs_the_destination_refuses_are_skipped() {
+        let tmp = tempfile::tempdir().unwrap();
+        let from = tmp.path().join("a");
+        write(&from, "a");
+        if xattr::set(&from, "user.origin", b"downloads").is_err() {
+            return; // No user xattrs on this filesystem.
+        }
+
+        let copy = tmp.path().join("b");
+        write(&copy, "a");
+        copy_xattrs(&from, &copy).unwrap();
+        assert_eq!(
+            xattr::get(&copy, "user.origin").unwrap().as_deref(),
+            Some(&b"downloads"[..])
+        );
+
+        // Linux only allows `user.*` names on regular files and folders.
+        let link = tmp.path().join("link");
+        std::os::unix::fs::symlink(&from, &link).unwrap();
+        copy_xattrs(&from, &link).unwrap();
+    }
+}
@@ -0,0 +1,852 @@
+use crate::cache::Pruned;
+use crate::checksum::{Algorithm, Verified};
+use crate::clean::CleanItem;
+use crate::compress::Compression;
+use crate::config::Config;
+use crate::dupes::{DupeGroup, Found, Keep};
+use crate::filter::Excluded;
+use crate::journal::UndoReport;
+use crate::kind::{Ecosystem, ProjectKind, Vcs};
+use crate::manifest::Listed;
+use crate::restore::RestorePlan;
+use crate::{ArchivePlan, HeldBack, Mismatch, OrganizeMove, ProjectItem, ScanReport};
+use anyhow::Result;
+use chrono::{DateTime, Local, SecondsFormat};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// Machine-readable output. Every document carries `schema_version`; bump it
+// whenever a field is renamed, removed or changes meaning. Adding fields is fine.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable report
+    #[default]
+    Text,
+    /// One JSON document
+    Json,
+    /// One JSON object per line, one line per item
+    Ndjson,
+    /// Header row plus one row per item
+    Csv,
+}
+
+pub fn rfc3339(t: SystemTime) -> String {
+    let dt: DateTime<Local> = t.into();
+    dt.to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+// A flat row type: serialized as-is for JSON/NDJSON, and column by column for CSV.
+pub trait Record: Serialize {
+    const COLUMNS: &'static [&'static str];
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectRecord {
+    pub status: &'static str,
+    pub root: Option<PathBuf>,
+    pub path: PathBuf,
+    pub kind: ProjectKind,
+    pub vcs: Option<Vcs>,
+    pub last_activity: String,
+    pub activity_source: &'static str,
+    pub apparent_bytes: Option<u64>,
+    pub disk_bytes: Option<u64>,
+    pub file_count: Option<u64>,
+    pub reasons: Vec<String>,
+}
+
+impl Record for ProjectRecord {
+    const COLUMNS: &'static [&'static str] = &[
+        "status",
+        "root",
+        "path",
+        "kind",
+        "vcs",
+        "last_activity",
+        "activity_source",
+        "apparent_bytes",
+        "disk_bytes",
+        "file_count",
+        "reasons",
+    ];
+}
+
+impl ProjectRecord {
+    fn new(
+        status: &'static str,
+        item: &ProjectItem,
+        report: &ScanReport,
+        reasons: Vec<String>,
+    ) -> Self {
+        ProjectRecord {
+            status,
+            root: report.root_of(item).map(|r| r.path.clone()),
+            path: item.path.clone(),
+            kind: item.kind,
+            vcs: item.vcs,
+            last_activity: rfc3339(item.last_modified),
+            activity_source: item.activity_source.label(),
+            apparent_bytes: item.size.map(|s| s.apparent_bytes),
+            disk_bytes: item.size.map(|s| s.disk_bytes),
+            file_count: item.size.map(|s| s.file_count),
+            reasons,
+        }
+    }
+
+    fn stale(status: &'static str, item: &ProjectItem, report: &ScanReport) -> Self {
+        let reason = format!(
+            "no activity for more than {} days (last: {} via {})",
+            threshold(report, item),
+            rfc3339(item.last_modified),
+            item.activity_source.label()
+        );
+        ProjectRecord::new(status, item, report, vec![reason])
+    }
+
+    fn fresh(item: &ProjectItem, report: &ScanReport) -> Self {
+        let reason = format!("active within the last {} days", threshold(report, item));
+        ProjectRecord::new("fresh", item, report, vec![reason])
+    }
+
+    fn held(h: &HeldBack, report: &ScanReport) -> Self {
+        ProjectRecord::new("held_back", &h.item, report, h.reasons.clone())
+    }
+}
+
+fn threshold(report: &ScanReport, item: &ProjectItem) -> u64 {
+    report
+        .root_of(item)
+        .map(|r| r.older_than_days)
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Serialize)]
+struct ExcludedSummary {
+    path: PathBuf,
+    rule: String,
+}
+
+// Per-root totals, next to the combined ones at the top of a document.
+#[derive(Debug, Serialize)]
+struct RootSummary {
+    path: PathBuf,
+    older_than_days: u64,
+    scanned_count: usize,
+    scan_duration_ms: u64,
+    stale_count: usize,
+    reclaimable_disk_bytes: u64,
+}
+
+fn root_summaries(report: &ScanReport) -> Vec<RootSummary> {
+    report
+        .roots
+        .iter()
+        .map(|root| {
+            let stale: Vec<&ProjectItem> = report
+                .stale
+                .iter()
+                .filter(|p| p.path.starts_with(&root.path))
+                .collect();
+            RootSummary {
+                path: root.path.clone(),
+                older_than_days: root.older_than_days,
+                scanned_count: root.scanned_count,
+                scan_duration_ms: root.duration.as_millis() as u64,
+                stale_count: stale.len(),
+                reclaimable_disk_bytes: stale
+                    .iter()
+                    .filter_map(|p| p.size)
+                    .map(|s| s.disk_bytes)
+                    .sum(),
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MoveRecord {
+    pub status: &'static str,
+    pub from: PathBuf,
+    pub to: Option<PathBuf>,
+    pub category: Option<String>,
+    pub last_activity: Option<String>,
+    pub activity_source: Option<&'static str>,
+    pub apparent_bytes: Option<u64>,
+    pub disk_bytes: Option<u64>,
+    pub file_count: Option<u64>,
+    pub reasons: Vec<String>,
+}
+
+impl Record for MoveRecord {
+    const COLUMNS: &'static [&'static str] = &[
+        "status",
+        "from",
+        "to",
+        "category",
+        "last_activity",
+        "activity_source",
+        "apparent_bytes",
+        "disk_bytes",
+        "file_count",
+        "reasons",
+    ];
+}
+
+impl MoveRecord {
+    fn project(status: &'static str, from: &Path, to: Option<&Path>, p: &ProjectRecord) -> Self {
+        MoveRecord {
+            status,
+            from: from.to_path_buf(),
+            to: to.map(Path::to_path_buf),
+            category: None,
+            last_activity: Some(p.last_activity.clone()),
+            activity_source: Some(p.activity_source),
+            apparent_bytes: p.apparent_bytes,
+            disk_bytes: p.disk_bytes,
+            file_count: p.file_count,
+            reasons: p.reasons.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MismatchRecord {
+    pub path: PathBuf,
+    pub extension: String,
+    pub content: &'static str,
+    pub content_mime: &'static str,
+    pub expected_extensions: Vec<&'static str>,
+}
+
+impl Record for MismatchRecord {
+    const COLUMNS: &'static [&'static str] = &[
+        "path",
+        "extension",
+        "content",
+        "content_mime",
+        "expected_extensions",
+    ];
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DupeRecord {
+    pub group: usize,
+    pub hash: String,
+    pub size: u64,
+    pub path: PathBuf,
+    // Hardlinks of `path`; they share its fate.
+    pub links: Vec<PathBuf>,
+    pub modified: String,
+    // "keep", "trash" or "hardlink" once a policy is chosen; None when only reporting.
+    pub action: Option<&'static str>,
+}
+
+impl Record for DupeRecord {
+    const COLUMNS: &'static [&'static str] = &[
+        "group", "hash", "size", "path", "links", "modified", "action",
+    ];
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchivedRecord {
+    pub bucket: String,
+    pub name: String,
+    pub path: PathBuf,
+    pub present: bool,
+    pub original_path: PathBuf,
+    pub archived_at: String,
+    pub last_activity: Option<String>,
+    pub activity_source: Option<String>,
+    pub apparent_bytes: Option<u64>,
+    pub disk_bytes: Option<u64>,
+    pub file_count: Option<u64>,
+    pub compression: Compression,
+    pub archived_bytes: Option<u64>,
+    pub checksum: Option<Algorithm>,
+    pub host: Option<String>,
+    pub sweeper_version: Option<String>,
+}
+
+impl Record for ArchivedRecord {
+    const COLUMNS: &'static [&'static str] = &[
+        "bucket",
+        "name",
+        "path",
+        "present",
+        "original_path",
+        "archived_at",
+        "last_activity",
+        "activity_source",
+        "apparent_bytes",
+        "disk_bytes",
+        "file_count",
+        "compression",
+        "archived_bytes",
+        "checksum",
+        "host",
+        "sweeper_version",
+    ];
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyRecord {
+    pub bucket: String,
+    pub name: String,
+    pub path: PathBuf,
+    // "ok", "damaged", "missing" or "unchecked".
+    pub status: &'static str,
+    pub algorithm: Option<Algorithm>,
+    pub file_count: usize,
+    pub missing: Vec<String>,
+    pub modified: Vec<String>,
+    pub extra: Vec<String>,
+    pub error: Option<String>,
+}
+
+impl Record for VerifyRecord {
+    const COLUMNS: &'static [&'static str] = &[
+        "bucket",
+        "name",
+        "path",
+        "status",
+        "algorithm",
+        "file_count",
+        "missing",
+        "modified",
+        "extra",
+        "error",
+    ];
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CleanRecord {
+    // "planned", "removed" or "kept".
+    pub status: &'static str,
+    pub project: PathBuf,
+    pub path: PathBuf,
+    pub ecosystem: Ecosystem,
+    pub apparent_bytes: u64,
+    pub disk_bytes: u64,
+    pub file_count: u64,
+    pub reason: Option<String>,
+}
+
+impl Record for CleanRecord {
+    const COLUMNS: &'static [&'static str] = &[
+        "status",
+        "project",
+        "path",
+        "ecosystem",
+        "apparent_bytes",
+        "disk_bytes",
+        "file_count",
+        "reason",
+    ];
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PruneRecord {
+    pub project: Option<PathBuf>,
+    pub file: PathBuf,
+    pub reason: &'static str,
+    pub bytes: u64,
+}
+
+impl Record for PruneRecord {
+    const COLUMNS: &'static [&'static str] = &["project", "file", "reason", "bytes"];
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UndoRecord {
+    pub action: &'static str,
+    pub path: Option<PathBuf>,
+    pub detail: Option<String>,
+}
+
+impl Record for UndoRecord {
+    const COLUMNS: &'static [&'static str] = &["action", "path", "detail"];
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigRecord {
+    pub key: String,
+    pub value: Value,
+    pub source: String,
+}
+
+impl Record for ConfigRecord {
+    const COLUMNS: &'static [&'static str] = &["key", "value", "source"];
+}
+
+pub struct Document {
+    kind: &'static str,
+    fields: Map<String, Value>,
+    rows_key: &'static str,
+    columns: &'static [&'static str],
+    rows: Vec<Value>,
+}
+
+impl Document {
+    fn new<R: Record>(kind: &'static str, rows_key: &'static str, rows: &[R]) -> Self {
+        let mut fields = Map::new();
+        fields.insert("generated_at".into(), rfc3339(SystemTime::now()).into());
+        Document {
+            kind,
+            fields,
+            rows_key,
+            columns: R::COLUMNS,
+            rows: rows.iter().map(to_value).collect(),
+        }
+    }
+
+    fn field(mut self, key: &str, value: impl Serialize) -> Self {
+        self.fields.insert(key.to_string(), to_value(&value));
+        self
+    }
+
+    // `root` and `older_than_days` are only set when there is a single one;
+    // `roots` always lists every root.
+    fn roots(self, report: &ScanReport) -> Self {
+        let root = match report.roots.as_slice() {
+            [only] => Some(&only.path),
+            _ => None,
+        };
+        self.field("root", root)
+            .field("roots", root_summaries(report))
+            .field("older_than_days", report.older_than_days())
+            .excluded(&report.excluded)
+    }
+
+    // What the include/exclude rules kept out, and which rule did.
+    pub fn excluded(self, excluded: &[Excluded]) -> Self {
+        let rows: Vec<ExcludedSummary> = excluded
+            .iter()
+            .map(|e| ExcludedSummary {
+                path: e.path.clone(),
+                rule: e.rule.clone(),
+            })
+            .collect();
+        self.field("excluded", rows)
+    }
+
+    // Text is printed by the callers themselves; this only handles the
+    // machine-readable formats.
+    pub fn write(&self, format: OutputFormat) -> Result<()> {
+        match format {
+            OutputFormat::Text => {}
+            OutputFormat::Json => {
+                let mut doc = self.header();
+                doc.extend(self.fields.clone());
+                doc.insert(self.rows_key.into(), Value::Array(self.rows.clone()));
+                println!("{}", serde_json::to_string_pretty(&doc)?);
+            }
+            OutputFormat::Ndjson => {
+                for row in &self.rows {
+                    let mut line = self.header();
+                    if let Value::Object(fields) = row {
+                        line.extend(fields.clone());
+                    }
+                    println!("{}", serde_json::to_string(&line)?);
+                }
+            }
+            OutputFormat::Csv => {
+                println!("{}", self.columns.join(","));
+                for row in &self.rows {
+                    let cells: Vec<String> = self
+                        .columns
+                        .iter()
+                        .map(|c| csv_escape(&csv_cell(row.get(*c))))
+                        .collect();
+                    println!("{}", cells.join(","));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn header(&self) -> Map<String, Value> {
+        let mut m = Map::new();
+        m.insert("schema_version".into(), SCHEMA_VERSION.into());
+        m.insert("kind".into(), self.kind.into());
+        m
+    }
+}
+
+fn to_value(v: &impl Serialize) -> Value {
+    serde_json::to_value(v).unwrap_or(Value::Null)
+}
+
+fn csv_cell(v: Option<&Value>) -> String {
+    match v {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|i| csv_cell(Some(i)))
+            .collect::<Vec<_>>()
+            .join("; "),
+        Some(other) => other.to_string(),
+    }
+}
+
+fn csv_escape(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+pub fn scan_document(report: &ScanReport, held: &[HeldBack]) -> Document {
+    let mut rows: Vec<ProjectRecord> = report
+        .stale
+        .iter()
+        .map(|i| ProjectRecord::stale("stale", i, report))
+        .collect();
+    rows.extend(held.iter().map(|h| ProjectRecord::held(h, report)));
+    rows.extend(report.fresh.iter().map(|i| ProjectRecord::fresh(i, report)));
+
+    // Stale projects per kind: how many, and what they take up.
+    let mut by_kind: BTreeMap<ProjectKind, KindSummary> = BTreeMap::new();
+    for item in &report.stale {
+        let k = by_kind.entry(item.kind).or_default();
+        k.stale_count += 1;
+        k.disk_bytes += item.size.map(|s| s.disk_bytes).unwrap_or(0);
+    }
+
+    let reclaimable = report.reclaimable();
+    Document::new("scan", "projects", &rows)
+        .roots(report)
+        .field("scanned_count", report.scanned_count())
+        .field("scan_duration_ms", report.duration().as_millis() as u64)
+        .field("reclaimable_apparent_bytes", reclaimable.apparent_bytes)
+        .field("reclaimable_disk_bytes", reclaimable.disk_bytes)
+        .field("reclaimable_file_count", reclaimable.file_count)
+        .field("stale_by_kind", by_kind)
+}
+
+#[derive(Debug, Default, Serialize)]
+struct KindSummary {
+    stale_count: usize,
+    disk_bytes: u64,
+}
+
+pub fn archive_document(
+    report: &ScanReport,
+    plan: &ArchivePlan,
+    held: &[HeldBack],
+    run_id: Option<&str>,
+) -> Document {
+    let status = if run_id.is_some() { "moved" } else { "planned" };
+
+    let mut rows: Vec<MoveRecord> = report
+        .stale
+        .iter()
+        .filter_map(|item| {
+            let mv = plan.moves.iter().find(|mv| mv.from == item.path)?;
+            let p = ProjectRecord::stale(status, item, report);
+            Some(MoveRecord::project(status, &mv.from, Some(&mv.to), &p))
+        })
+        .collect();
+    rows.extend(held.iter().map(|h| {
+        let p = ProjectRecord::held(h, report);
+        MoveRecord::project("held_back", &h.item.path, None, &p)
+    }));
+
+    Document::new("archive", "moves", &rows)
+        .roots(report)
+        .field("dest_root", &plan.dest_root)
+        .field("month_bucket", &plan.month_bucket)
+        .field("compression", plan.compression)
+        .field("checksum", plan.checksum)
+        .field("applied", run_id.is_some())
+        .field("run_id", run_id)
+}
+
+pub fn delete_document(report: &ScanReport, held: &[HeldBack], run_id: Option<&str>) -> Document {
+    let status = if run_id.is_some() { "trashed" } else { "stale" };
+
+    let mut rows: Vec<ProjectRecord> = report
+        .stale
+        .iter()
+        .map(|i| ProjectRecord::stale(status, i, report))
+        .collect();
+    rows.extend(held.iter().map(|h| ProjectRecord::held(h, report)));
+
+    Document::new("delete", "projects", &rows)
+        .roots(report)
+        .field("applied", run_id.is_some())
+        .field("run_id", run_id)
+}
+
+pub fn clean_document(
+    report: &ScanReport,
+    items: &[CleanItem],
+    applied: bool,
+    permanent: bool,
+    run_id: Option<&str>,
+) -> Document {
+    let status = match (applied, permanent) {
+        (false, _) => "planned",
+        (true, true) => "removed",
+        (true, false) => "trashed",
+    };
+
+    let rows: Vec<CleanRecord> = items
+        .iter()
+        .flat_map(|item| {
+            item.artifacts.iter().map(|a| CleanRecord {
+                status: if a.skipped.is_some() { "kept" } else { status },
+                project: item.project.path.clone(),
+                path: a.path.clone(),
+                ecosystem: a.ecosystem,
+                apparent_bytes: a.size.apparent_bytes,
+                disk_bytes: a.size.disk_bytes,
+                file_count: a.size.file_count,
+                reason: a.skipped.clone(),
+            })
+        })
+        .collect();
+
+    Document::new("clean", "artifacts", &rows)
+        .roots(report)
+        .field(
+            "reclaimable_bytes",
+            items.iter().map(CleanItem::reclaimable).sum::<u64>(),
+        )
+        .field("permanent", permanent)
+        .field("applied", applied)
+        .field("run_id", run_id)
+}
+
+pub fn organize_document(root: &Path, moves: &[OrganizeMove], run_id: Option<&str>) -> Document {
+    let status = if run_id.is_some() { "moved" } else { "planned" };
+
+    let rows: Vec<MoveRecord> = moves
+        .iter()
+        .map(|mv| MoveRecord {
+            status: if mv.identical {
+                "skipped_identical"
+            } else {
+                status
+            },
+            from: mv.from.clone(),
+            to: Some(mv.to.clone()),
+            category: Some(mv.category.clone()),
+            last_activity: None,
+            activity_source: None,
+            apparent_bytes: None,
+            disk_bytes: None,
+            file_count: None,
+            reasons: vec![mv.reason.clone()],
+        })
+        .collect();
+
+    Document::new("organize", "moves", &rows)
+        .field("root", root)
+        .field("applied", run_id.is_some())
+        .field("run_id", run_id)
+}
+
+pub fn mismatch_document(root: &Path, found: &[Mismatch]) -> Document {
+    let rows: Vec<MismatchRecord> = found
+        .iter()
+        .map(|m| MismatchRecord {
+            path: m.path.clone(),
+            extension: m.extension.clone(),
+            content: m.content.name,
+            content_mime: m.content.mime,
+            expected_extensions: m.content.extensions.to_vec(),
+        })
+        .collect();
+
+    Document::new("mismatches", "files", &rows).field("root", root)
+}
+
+pub fn dupes_document(
+    root: &Path,
+    found: &Found,
+    keep: Option<Keep>,
+    action: Option<&'static str>,
+    applied: bool,
+    run_id: Option<&str>,
+) -> Document {
+    let groups = &found.groups;
+    let mut rows = Vec::new();
+    for (i, g) in groups.iter().enumerate() {
+        let kept = keep.map(|k| g.split(k).0.path.clone());
+        for f in &g.files {
+            let action = match &kept {
+                Some(k) if *k == f.path => Some("keep"),
+                _ => action,
+            };
+            rows.push(DupeRecord {
+                group: i + 1,
+                hash: g.hash.clone(),
+                size: g.size,
+                path: f.path.clone(),
+                links: f.links.clone(),
+                modified: rfc3339(f.modified),
+                action,
+            });
+        }
+    }
+
+    let wasted: u64 = groups.iter().map(DupeGroup::wasted).sum();
+    Document::new("dupes", "files", &rows)
+        .field("root", root)
+        .field("group_count", groups.len())
+        .field("wasted_bytes", wasted)
+        .field("unreadable", &found.unreadable)
+        .field("keep", keep)
+        .field("applied", applied)
+        .field("run_id", run_id)
+}
+
+pub fn archive_list_document(dest_root: &Path, listed: &[Listed]) -> Document {
+    let rows: Vec<ArchivedRecord> = listed
+        .iter()
+        .map(|l| {
+            let e = &l.entry;
+            ArchivedRecord {
+                bucket: l.bucket.clone(),
+                name: e.name.clone(),
+                path: l.path.clone(),
+                present: l.present,
+                original_path: e.original_path.clone(),
+                archived_at: e.archived_at.to_rfc3339_opts(SecondsFormat::Secs, false),
+                last_activity: e
+                    .last_activity
+                    .map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, false)),
+                activity_source: e.activity_source.clone(),
+                apparent_bytes: e.apparent_bytes,
+                disk_bytes: e.disk_bytes,
+                file_count: e.file_count,
+                compression: e.compression,
+                archived_bytes: e.archived_bytes,
+                checksum: e.checksum,
+                host: e.host.clone(),
+                sweeper_version: e.sweeper_version.clone(),
+            }
+        })
+        .collect();
+
+    Document::new("archive_list", "entries", &rows).field("dest_root", dest_root)
+}
+
+pub fn verify_document(archive_dir: &Path, verified: &[Verified]) -> Document {
+    let rows: Vec<VerifyRecord> = verified
+        .iter()
+        .map(|v| VerifyRecord {
+            bucket: v.bucket.clone(),
+            name: v.name.clone(),
+            path: v.path.clone(),
+            status: v.status.label(),
+            algorithm: v.algorithm,
+            file_count: v.file_count,
+            missing: v.diff.missing.clone(),
+            modified: v.diff.modified.clone(),
+            extra: v.diff.extra.clone(),
+            error: v.error.clone(),
+        })
+        .collect();
+
+    Document::new("verify", "projects", &rows)
+        .field("archive_dir", archive_dir)
+        .field("failed", verified.iter().filter(|v| v.failed()).count())
+}
+
+pub fn cache_prune_document(index_dir: &Path, pruned: &[Pruned]) -> Document {
+    let rows: Vec<PruneRecord> = pruned
+        .iter()
+        .map(|p| PruneRecord {
+            project: p.project.clone(),
+            file: p.file.clone(),
+            reason: p.reason,
+            bytes: p.bytes,
+        })
+        .collect();
+
+    Document::new("cache_prune", "entries", &rows)
+        .field("index_dir", index_dir)
+        .field("freed_bytes", pruned.iter().map(|p| p.bytes).sum::<u64>())
+}
+
+pub fn restore_document(plan: &RestorePlan, run_id: Option<&str>) -> Document {
+    let rows = [MoveRecord {
+        status: if run_id.is_some() {
+            "restored"
+        } else {
+            "planned"
+        },
+        from: plan.from.clone(),
+        to: Some(plan.to.clone()),
+        category: None,
+        last_activity: None,
+        activity_source: None,
+        apparent_bytes: None,
+        disk_bytes: None,
+        file_count: None,
+        reasons: plan.note.iter().cloned().collect(),
+    }];
+
+    Document::new("restore", "moves", &rows)
+        .field("applied", run_id.is_some())
+        .field("run_id", run_id)
+}
+
+pub fn undo_document(report: &UndoReport) -> Document {
+    let mut rows: Vec<UndoRecord> = report
+        .run
+        .moves
+        .iter()
+        .map(|mv| UndoRecord {
+            action: "moved_back",
+            path: Some(mv.to.clone()),
+            detail: Some(format!("from {}", mv.from.display())),
+        })
+        .collect();
+    rows.extend(report.run.packed.iter().map(|mv| UndoRecord {
+        action: "repacked",
+        path: Some(mv.to.clone()),
+        detail: Some(format!("from {}", mv.from.display())),
+    }));
+    rows.extend(report.run.unpacked.iter().map(|mv| UndoRecord {
+        action: "unpacked",
+        path: Some(mv.to.clone()),
+        detail: Some(format!("from {}", mv.from.display())),
+    }));
+    rows.extend(report.run.restored.iter().map(|p| UndoRecord {
+        action: "restored",
+        path: Some(p.clone()),
+        detail: None,
+    }));
+    rows.extend(report.conflicts.iter().map(|c| UndoRecord {
+        action: "conflict",
+        path: None,
+        detail: Some(c.clone()),
+    }));
+
+    Document::new("undo", "actions", &rows)
+        .field("run_id", &report.run.id)
+        .field("undone_run_id", &report.undone_run_id)
+}
+
+pub fn config_document(config: &Config) -> Document {
+    let rows: Vec<ConfigRecord> = config
+        .entries
+        .iter()
+        .map(|(key, (value, source))| ConfigRecord {
+            key: key.clone(),
+            value: to_value(value),
+            source: source.to_string(),
+        })
+        .collect();
+
+    Document::new("config", "settings", &rows).field("files", &config.files)
+}
@@ -0,0 +1,327 @@
+use crate::checksum;
+use crate::compress::{self, Compression};
+use crate::manifest::{self, Manifest, ManifestEntry};
+use crate::{avoid_collision, journal, mover};
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Bringing archived projects back to where they were archived from.
+
+#[derive(Debug, Clone)]
+pub struct Archived {
+    pub path: PathBuf,
+    // e.g. "2026-02"
+    pub bucket: String,
+    pub name: String,
+    // None for folders archived before manifests existed or moved in by hand.
+    pub entry: Option<ManifestEntry>,
+}
+
+impl Archived {
+    // Packed into a tarball rather than kept as a folder.
+    pub fn compression(&self) -> Compression {
+        Compression::from_path(&self.path).unwrap_or_default()
+    }
+
+    // The project's own name: from the manifest, else the archived name without
+    // any tarball extension.
+    fn original_name(&self) -> String {
+        self.entry
+            .as_ref()
+            .and_then(|e| e.original_path.file_name())
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.compression().strip_extension(&self.name))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RestorePlan {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    // Why `to` isn't simply the recorded original path, if it isn't.
+    pub note: Option<String>,
+}
+
+// Everything archived under `dest_root`, oldest bucket first.
+pub fn list_archived(dest_root: &Path) -> Result<Vec<Archived>> {
+    let buckets = manifest::buckets(dest_root)?;
+
+    let mut found = Vec::new();
+    for bucket_dir in buckets {
+        let manifest = Manifest::load(&bucket_dir)?;
+        let bucket = bucket_dir
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let mut names: Vec<String> = fs::read_dir(&bucket_dir)
+            .with_context(|| format!("Cannot read bucket: {}", bucket_dir.display()))?
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            // Hidden files and tarballs still being written aren't projects.
+            .filter(|n| !n.starts_with('.') && !n.ends_with(".partial"))
+            .collect();
+        names.sort();
+
+        for name in names {
+            found.push(Archived {
+                path: bucket_dir.join(&name),
+                bucket: bucket.clone(),
+                entry: manifest.get(&name).cloned(),
+                name,
+            });
+        }
+    }
+    Ok(found)
+}
+
+// An archived folder given by path, e.g. `dest/2026-02/foo`.
+pub fn archived_at(path: &Path) -> Result<Archived> {
+    let path = path
+        .canonicalize()
+        .with_context(|| format!("Cannot access path: {}", path.display()))?;
+    let (Some(bucket_dir), Some(name)) = (path.parent(), path.file_name()) else {
+        bail!("Not an archived project: {}", path.display());
+    };
+    let name = name.to_string_lossy().to_string();
+    let manifest = Manifest::load(bucket_dir)?;
+    Ok(Archived {
+        entry: manifest.get(&name).cloned(),
+        bucket: bucket_dir
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        path,
+        name,
+    })
+}
+
+// Archived projects matching `query`, best matches only: an exact name (in the
+// archive or originally), else a case-insensitive substring, else names within
+// a couple of typos.
+pub fn find(archived: Vec<Archived>, query: &str) -> Vec<Archived> {
+    let exact: Vec<Archived> = archived
+        .iter()
+        .filter(|a| a.name == query || a.original_name() == query)
+        .cloned()
+        .collect();
+    if !exact.is_empty() {
+        return exact;
+    }
+
+    let q = query.to_lowercase();
+    let names = |a: &Archived| [a.name.to_lowercase(), a.original_name().to_lowercase()];
+
+    let partial: Vec<Archived> = archived
+        .iter()
+        .filter(|a| names(a).iter().any(|n| n.contains(&q)))
+        .cloned()
+        .collect();
+    if !partial.is_empty() {
+        return partial;
+    }
+
+    archived
+        .into_iter()
+        .filter(|a| names(a).iter().any(|n| edit_distance(n, &q) <= 2))
+        .collect()
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+// Where the project goes back to: its recorded original path, or `into/<name>`
+// when a folder is given. An occupied target gets a `_N` suffix. Tarballs are
+// unpacked on the way.
+pub fn plan_restore(item: &Archived, into: Option<&Path>) -> Result<RestorePlan> {
+    let target = match (into, &item.entry) {
+        (Some(dir), _) => dir.join(item.original_name()),
+        (None, Some(entry)) => entry.original_path.clone(),
+        (None, None) => bail!(
+            "No record of where '{}' was archived from; pass --to DIR",
+            item.path.display()
+        ),
+    };
+
+    let to = avoid_collision(&target);
+    let note = (to != target).then(|| {
+        format!(
+            "{} already exists; restoring as {}",
+            target.display(),
+            to.display()
+        )
+    });
+
+    Ok(RestorePlan {
+        from: item.path.clone(),
+        to,
+        note,
+    })
+}
+
+pub fn apply_restore(plan: &RestorePlan, run: &mut journal::Run) -> Result<()> {
+    if let Some(parent) = plan.to.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create dir: {}", parent.display()))?;
+    }
+    match Compression::from_path(&plan.from) {
+        Some(compression) if plan.from.is_file() => {
+            compress::unpack(&plan.from, &plan.to, compression)?;
+            run.record_unpack(&plan.from, &plan.to);
+        }
+        _ => {
+            mover::move_path(&plan.from, &plan.to, None)?;
+            run.record_move(&plan.from, &plan.to);
+        }
+    }
+    // Its checksums describe archived content, which this no longer is. They
+    // go to the bin, so undoing the restore puts them back.
+    checksum::trash(&plan.from, run)
+}
+
+pub fn print_candidates(items: &[Archived]) {
+    for a in items {
+        match &a.entry {
+            Some(e) => println!(
+                "  - {}  (from {})",
+                a.path.display(),
+                e.original_path.display()
+            ),
+            None => println!("  - {}", a.path.display()),
+        }
+    }
+}
+
+pub fn print_restore(plan: &RestorePlan) {
+    println!(
+        "Restore: '{}' -> '{}'",
+        plan.from.display(),
+        plan.to.display()
+    );
+    if let Some(note) = &plan.note {
+        println!("  note: {}", note);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::Algorithm;
+    use chrono::Local;
+
+    fn archived(name: &str, original: Option<&str>) -> Archived {
+        let entry = original.map(|path| {
+            serde_json::from_value(serde_json::json!({
+                "name": name,
+                "original_path": path,
+                "archived_at": Local::now(),
+            }))
+            .unwrap()
+        });
+        Archived {
+            path: PathBuf::from("/archive/2026-01").join(name),
+            bucket: "2026-01".to_string(),
+            name: name.to_string(),
+            entry,
+        }
+    }
+
+    fn names(found: Vec<Archived>) -> Vec<String> {
+        found.into_iter().map(|a| a.name).collect()
+    }
+
+    #[test]
+    fn find_prefers_exact_then_partial_then_close_names() {
+        let all = vec![
+            archived("app", Some("/code/app")),
+            archived("app_1", Some("/code/other/app")),
+            archived("my-app.tar.zst", None),
+            archived("website", Some("/code/website")),
+        ];
+
+        // The second one is called `app` where it came from.
+        assert_eq!(names(find(all.clone(), "app")), ["app", "app_1"]);
+        assert_eq!(
+            names(find(all.clone(), "APP")),
+            ["app", "app_1", "my-app.tar.zst"]
+        );
+        assert_eq!(names(find(all.clone(), "my-app")), ["my-app.tar.zst"]);
+        assert_eq!(names(find(all.clone(), "websiet")), ["website"]);
+        assert!(find(all, "database").is_empty());
+    }
+
+    #[test]
+    fn edit_distance_counts_single_character_edits() {
+        assert_eq!(edit_distance("website", "website"), 0);
+        assert_eq!(edit_distance("website", "websiet"), 2);
+        assert_eq!(edit_distance("app", "apps"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn plan_restore_goes_back_to_the_original_path_or_next_to_it() {
+        let tmp = tempfile::tempdir().unwrap();
+        let original = tmp.path().join("code/app");
+        let item = archived("app", Some(&original.to_string_lossy()));
+
+        let plan = plan_restore(&item, None).unwrap();
+        assert_eq!(plan.to, original);
+        assert!(plan.note.is_none());
+
+        fs::create_dir_all(&original).unwrap();
+        let plan = plan_restore(&item, None).unwrap();
+        assert_eq!(plan.to, tmp.path().join("code/app_1"));
+        assert!(plan.note.unwrap().contains("already exists"));
+
+        let into = tmp.path().join("elsewhere");
+        assert_eq!(
+            plan_restore(&item, Some(&into)).unwrap().to,
+            into.join("app")
+        );
+
+        // Without a manifest entry, only `--to` says where, and the tarball
+        // extension is dropped from the name.
+        let bare = archived("tool.tar.gz", None);
+        assert!(plan_restore(&bare, None).is_err());
+        assert_eq!(
+            plan_restore(&bare, Some(&into)).unwrap().to,
+            into.join("tool")
+        );
+    }
+
+    #[test]
+    fn restoring_sends_the_checksums_to_the_bin() {
+        let tmp = tempfile::tempdir().unwrap();
+        let from = tmp.path().join("archive/2026-01/app");
+        fs::create_dir_all(&from).unwrap();
+        fs::write(from.join("main.rs"), "fn main() {}\n").unwrap();
+        let sums = checksum::hash_tree(&from, Algorithm::Blake3).unwrap();
+        checksum::save(&from, &sums).unwrap();
+
+        let plan = RestorePlan {
+            from: from.clone(),
+            to: tmp.path().join("code/app"),
+            note: None,
+        };
+        let mut run = journal::Run::new("restore");
+        apply_restore(&plan, &mut run).unwrap();
+
+        assert!(plan.to.join("main.rs").is_file());
+        assert!(checksum::load(&from).unwrap().is_none());
+        assert_eq!(run.moves.len(), 1);
+        assert_eq!(run.trashed.len(), 1);
+        assert!(run.trashed[0].ends_with(".sweeper-checksums/app.json"));
+    }
+}
@@ -0,0 +1,513 @@
+use crate::sniff::{self, Kind};
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Local};
+use globset::{GlobBuilder, GlobMatcher};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// Organize rules. A file goes to the `target` of the first rule whose conditions
+// all match. User rules (from config) are tried in priority order, highest first,
+// then the built-in categories, then `Other`.
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OrganizeRule {
+    pub name: Option<String>,
+    // Higher runs first; equal priorities keep their order in the config.
+    pub priority: i32,
+    pub extensions: Vec<String>,
+    // Glob on the file name, e.g. "invoice-*.pdf".
+    pub glob: Option<String>,
+    // Regex on the file name.
+    pub regex: Option<String>,
+    pub min_size: Option<ByteSize>,
+    pub max_size: Option<ByteSize>,
+    // Age from the file's mtime, in days.
+    pub min_age_days: Option<u64>,
+    pub max_age_days: Option<u64>,
+    // "image/png", or a whole family with "image/*".
+    pub mime: Option<String>,
+    // Folder under the organized dir. Placeholders: {year} {month} {day} from the
+    // file's mtime, {ext}, and {mime_type} (e.g. "image").
+    pub target: String,
+}
+
+// Byte count written either as a number or as "10MB", "1.5GiB", ...
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteSize(pub u64);
+
+impl Serialize for ByteSize {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> std::result::Result<S::Ok, S::Error> {
+        s.serialize_u64(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Number(u64),
+            Text(String),
+        }
+        match Repr::deserialize(d)? {
+            Repr::Number(n) => Ok(ByteSize(n)),
+            Repr::Text(s) => parse_size(&s)
+                .map(ByteSize)
+                .map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+fn parse_size(s: &str) -> std::result::Result<u64, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let num: f64 = num.parse().map_err(|_| format!("invalid size '{}'", s))?;
+    let mult: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1_000,
+        "kib" => 1 << 10,
+        "m" | "mb" => 1_000_000,
+        "mib" => 1 << 20,
+        "g" | "gb" => 1_000_000_000,
+        "gib" => 1 << 30,
+        "t" | "tb" => 1_000_000_000_000,
+        "tib" => 1 << 40,
+        other => return Err(format!("unknown size unit '{}' in '{}'", other, s)),
+    };
+    Ok((num * mult as f64) as u64)
+}
+
+// The built-in categories, as rules.
+fn default_rules() -> Vec<OrganizeRule> {
+    let rule = |name: &str, exts: &[&str]| OrganizeRule {
+        name: Some(name.to_string()),
+        extensions: exts.iter().map(|e| e.to_string()).collect(),
+        target: name.to_string(),
+        ..Default::default()
+    };
+    vec![
+        rule("Documents", &["pdf", "doc", "docx", "txt"]),
+        rule("Images", &["jpg", "png", "gif", "webp"]),
+        rule("Archives", &["zip", "rar", "7z", "tar", "gz"]),
+        rule("Installers", &["dmg", "exe", "msi", "pkg", "deb", "rpm"]),
+        rule("Spreadsheets", &["csv", "xlsx"]),
+        rule("Other", &[]),
+    ]
+}
+
+// What rules get to look at for one file.
+#[derive(Debug, Clone)]
+pub struct FileFacts {
+    pub name: String,
+    // Extension used for matching: the file's own, or the one implied by its
+    // content when sniffing found that the two disagree.
+    pub ext: String,
+    pub size: u64,
+    pub modified: SystemTime,
+    pub mime: Option<String>,
+    // Format recognized from the first bytes, when sniffing is on.
+    pub content: Option<Kind>,
+    // The extension as written in the file name.
+    pub name_ext: String,
+}
+
+impl FileFacts {
+    pub fn from_path(path: &Path, sniff: bool) -> Result<Self> {
+        let meta =
+            fs::metadata(path).with_context(|| format!("Failed to stat: {}", path.display()))?;
+        let name = path
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let name_ext = path
+            .extension()
+            .and_then(|s| s.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+
+        let content = if sniff { sniff::sniff(path) } else { None };
+        let (ext, mime) = match content {
+            Some(kind) if kind.accepts(&name_ext) => (name_ext.clone(), Some(kind.mime.into())),
+            Some(kind) => (kind.ext.to_string(), Some(kind.mime.into())),
+            None => {
+                let mime = mime_guess::from_ext(&name_ext)
+                    .first()
+                    .map(|m| m.essence_str().to_string());
+                (name_ext.clone(), mime)
+            }
+        };
+
+        Ok(FileFacts {
+            name,
+            ext,
+            size: meta.len(),
+            modified: meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            mime,
+            content,
+            name_ext,
+        })
+    }
+
+    // The content says something other than the extension does.
+    pub fn mismatch(&self) -> Option<Kind> {
+        self.content.filter(|k| !k.accepts(&self.name_ext))
+    }
+}
+
+struct CompiledRule {
+    rule: OrganizeRule,
+    glob: Option<GlobMatcher>,
+    regex: Option<Regex>,
+}
+
+pub struct RuleSet {
+    rules: Vec<CompiledRule>,
+}
+
+// The outcome for one file: where it goes and why.
+#[derive(Debug, Clone)]
+pub struct Placement {
+    pub target: PathBuf,
+    pub reason: String,
+}
+
+impl RuleSet {
+    pub fn new(user_rules: &[OrganizeRule]) -> Result<Self> {
+        let mut ordered: Vec<OrganizeRule> = user_rules.to_vec();
+        // sort_by_key is stable, so equal priorities keep config order.
+        ordered.sort_by_key(|r| std::cmp::Reverse(r.priority));
+        ordered.extend(default_rules());
+
+        let mut rules = Vec::with_capacity(ordered.len());
+        for rule in ordered {
+            rules.push(compile(rule)?);
+        }
+        Ok(RuleSet { rules })
+    }
+
+    pub fn place(&self, facts: &FileFacts) -> Placement {
+        for c in &self.rules {
+            if let Some(why) = c.matches(facts) {
+                let mut reason = format!("{}: {}", c.label(), why);
+                if let Some(kind) = facts.mismatch() {
+                    reason.push_str(&format!(" (content is {})", kind.name));
+                }
+                return Placement {
+                    target: render(&c.rule.target, facts),
+                    reason,
+                };
+            }
+        }
+        // Unreachable in practice: the default `Other` rule matches everything.
+        Placement {
+            target: PathBuf::from("Other"),
+            reason: "no rule matched".to_string(),
+        }
+    }
+}
+
+fn compile(rule: OrganizeRule) -> Result<CompiledRule> {
+    let label = rule.name.clone().unwrap_or_else(|| rule.target.clone());
+    if rule.target.trim().is_empty() {
+        bail!("Organize rule '{}' has an empty target", label);
+    }
+    check_template(&rule.target).with_context(|| format!("Organize rule '{}'", label))?;
+
+    let glob = match &rule.glob {
+        Some(g) => Some(
+            GlobBuilder::new(g)
+                .case_insensitive(true)
+                .build()
+                .with_context(|| format!("Organize rule '{}': invalid glob '{}'", label, g))?
+                .compile_matcher(),
+        ),
+        None => None,
+    };
+    let regex = match &rule.regex {
+        Some(r) => Some(
+            Regex::new(r)
+                .with_context(|| format!("Organize rule '{}': invalid regex '{}'", label, r))?,
+        ),
+        None => None,
+    };
+
+    Ok(CompiledRule { rule, glob, regex })
+}
+
+const PLACEHOLDERS: &[&str] = &["year", "month", "day", "ext", "mime_type"];
+
+// Targets must be relative and stay inside the organized folder.
+fn check_template(template: &str) -> Result<()> {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .with_context(|| format!("unclosed '{{' in target '{}'", template))?;
+        let key = &rest[start + 1..start + end];
+        if !PLACEHOLDERS.contains(&key) {
+            bail!(
+                "unknown placeholder '{{{}}}' in target '{}' (known: {})",
+                key,
+                template,
+                PLACEHOLDERS.join(", ")
+            );
+        }
+        rest = &rest[start + end + 1..];
+    }
+
+    if !Path::new(template)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+    {
+        bail!("target '{}' must be a relative path without '..'", template);
+    }
+    Ok(())
+}
+
+fn render(template: &str, facts: &FileFacts) -> PathBuf {
+    let dt: DateTime<Local> = facts.modified.into();
+    let ext = if facts.ext.is_empty() {
+        "noext"
+    } else {
+        facts.ext.as_str()
+    };
+    let mime_type = facts
+        .mime
+        .as_deref()
+        .and_then(|m| m.split('/').next())
+        .unwrap_or("unknown");
+
+    let rendered = template
+        .replace("{year}", &dt.format("%Y").to_string())
+        .replace("{month}", &dt.format("%m").to_string())
+        .replace("{day}", &dt.format("%d").to_string())
+        .replace("{ext}", ext)
+        .replace("{mime_type}", mime_type);
+    PathBuf::from(rendered)
+}
+
+impl CompiledRule {
+    fn label(&self) -> String {
+        match &self.rule.name {
+            Some(name) => format!("rule '{}'", name),
+            None => format!("rule -> '{}'", self.rule.target),
+        }
+    }
+
+    // All conditions must hold. Returns a short description of what matched.
+    fn matches(&self, f: &FileFacts) -> Option<String> {
+        let r = &self.rule;
+        let mut why = Vec::new();
+
+        if !r.extensions.is_empty() {
+            // A sniffed file also answers to its format's usual extension, so
+            // `photo.jpeg` with JPEG content still lands with the "jpg" rule.
+            let content_ext = f.content.map(|k| k.ext).filter(|e| !e.is_empty());
+            let ext = [Some(f.ext.as_str()), content_ext]
+                .into_iter()
+                .flatten()
+                .find(|ext| r.extensions.iter().any(|e| e.eq_ignore_ascii_case(ext)))?;
+            why.push(format!("extension '{}'", ext));
+        }
+        if let Some(glob) = &self.glob {
+            if !glob.is_match(&f.name) {
+                return None;
+            }
+            why.push(format!("glob '{}'", glob.glob()));
+        }
+        if let Some(re) = &self.regex {
+            if !re.is_match(&f.name) {
+                return None;
+            }
+            why.push(format!("regex '{}'", re.as_str()));
+        }
+        if let Some(min) = r.min_size {
+            if f.size < min.0 {
+                return None;
+            }
+            why.push(format!("size >= {}", min.0));
+        }
+        if let Some(max) = r.max_size {
+            if f.size > max.0 {
+                return None;
+            }
+            why.push(format!("size <= {}", max.0));
+        }
+
+        let age = SystemTime::now()
+            .duration_since(f.modified)
+            .unwrap_or(Duration::ZERO);
+        let days = age.as_secs() / (24 * 60 * 60);
+        if let Some(min) = r.min_age_days {
+            if days < min {
+                return None;
+            }
+            why.push(format!("older than {} days", min));
+        }
+        if let Some(max) = r.max_age_days {
+            if days > max {
+                return None;
+            }
+            why.push(format!("at most {} days old", max));
+        }
+
+        if let Some(want) = &r.mime {
+            let have = f.mime.as_deref()?;
+            let ok = match want.strip_suffix("/*") {
+                Some(family) => have.split('/').next() == Some(family),
+                None => have.eq_ignore_ascii_case(want),
+            };
+            if !ok {
+                return None;
+            }
+            why.push(format!("mime '{}'", have));
+        }
+
+        if why.is_empty() {
+            why.push("catch-all".to_string());
+        }
+        Some(why.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn facts(name: &str, size: u64) -> FileFacts {
+        let ext = Path::new(name)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        FileFacts {
+            name: name.to_string(),
+            ext: ext.clone(),
+            size,
+            modified: SystemTime::now(),
+            mime: mime_guess::from_ext(&ext)
+                .first()
+                .map(|m| m.essence_str().to_string()),
+            content: None,
+            name_ext: ext,
+        }
+    }
+
+    fn rule(name: &str, priority: i32, target: &str) -> OrganizeRule {
+        OrganizeRule {
+            name: Some(name.to_string()),
+            priority,
+            target: target.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parse_size_takes_decimal_and_binary_units() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("10b"), Ok(10));
+        assert_eq!(parse_size("10KB"), Ok(10_000));
+        assert_eq!(parse_size("1 KiB"), Ok(1024));
+        assert_eq!(parse_size(" 1.5GiB "), Ok(3 << 29));
+        assert_eq!(parse_size("2tb"), Ok(2_000_000_000_000));
+        assert!(parse_size("MB").is_err());
+        assert!(
+            parse_size("10 parsecs")
+                .unwrap_err()
+                .contains("unknown size unit")
+        );
+        assert!(parse_size("1.2.3MB").is_err());
+    }
+
+    #[test]
+    fn sizes_in_config_can_be_numbers_or_text() {
+        let rule: OrganizeRule =
+            toml::from_str("min_size = \"2MB\"\nmax_size = 4096\ntarget = \"Big\"").unwrap();
+        assert_eq!(rule.min_size, Some(ByteSize(2_000_000)));
+        assert_eq!(rule.max_size, Some(ByteSize(4096)));
+        assert!(toml::from_str::<OrganizeRule>("min_size = \"huge\"\ntarget = \"x\"").is_err());
+    }
+
+    #[test]
+    fn targets_must_be_known_placeholders_and_stay_inside() {
+        assert!(check_template("Photos/{year}/{month}").is_ok());
+        assert!(check_template("By type/{mime_type}/{ext}").is_ok());
+        assert!(check_template("{day}").is_ok());
+
+        let err = check_template("Photos/{yaer}").unwrap_err().to_string();
+        assert!(err.contains("unknown placeholder '{yaer}'"), "{}", err);
+        assert!(check_template("Photos/{year").is_err());
+        assert!(check_template("../Photos").is_err());
+        assert!(check_template("/tmp/Photos").is_err());
+        assert!(check_template("Photos/../..").is_err());
+
+        let mut bad = rule("bad", 0, "Out/{nope}");
+        assert!(RuleSet::new(std::slice::from_ref(&bad)).is_err());
+        bad.target = "  ".to_string();
+        assert!(RuleSet::new(&[bad]).is_err());
+    }
+
+    #[test]
+    fn higher_priority_wins_and_ties_keep_config_order() {
+        let pdf = |name: &str, priority: i32| OrganizeRule {
+            extensions: vec!["pdf".to_string()],
+            ..rule(name, priority, name)
+        };
+        let rules = RuleSet::new(&[pdf("first", 0), pdf("second", 0), pdf("urgent", 5)]).unwrap();
+        assert_eq!(
+            rules.place(&facts("a.pdf", 1)).target,
+            PathBuf::from("urgent")
+        );
+
+        let rules = RuleSet::new(&[pdf("first", 0), pdf("second", 0)]).unwrap();
+        let placed = rules.place(&facts("a.pdf", 1));
+        assert_eq!(placed.target, PathBuf::from("first"));
+        assert_eq!(placed.reason, "rule 'first': extension 'pdf'");
+    }
+
+    #[test]
+    fn the_first_rule_whose_conditions_all_hold_is_used() {
+        let invoices = OrganizeRule {
+            glob: Some("invoice-*".to_string()),
+            min_size: Some(ByteSize(100)),
+            ..rule("invoices", 1, "Invoices/{year}")
+        };
+        let rules = RuleSet::new(&[invoices]).unwrap();
+
+        // Too small for the user rule, so the built-in category takes it.
+        let small = rules.place(&facts("Invoice-7.pdf", 10));
+        assert_eq!(small.target, PathBuf::from("Documents"));
+
+        let big = rules.place(&facts("Invoice-7.pdf", 1000));
+        let year = Local::now().format("%Y").to_string();
+        assert_eq!(big.target, Path::new("Invoices").join(year));
+        assert!(big.reason.contains("glob 'invoice-*'"));
+
+        let other = rules.place(&facts("notes", 1));
+        assert_eq!(other.target, PathBuf::from("Other"));
+        assert_eq!(other.reason, "rule 'Other': catch-all");
+    }
+
+    #[test]
+    fn mime_families_match_with_a_wildcard() {
+        let images = OrganizeRule {
+            mime: Some("image/*".to_string()),
+            ..rule("pics", 0, "Pictures/{mime_type}/{ext}")
+        };
+        let rules = RuleSet::new(&[images]).unwrap();
+        assert_eq!(
+            rules.place(&facts("cat.webp", 1)).target,
+            Path::new("Pictures/image/webp")
+        );
+        assert_eq!(
+            rules.place(&facts("cat.txt", 1)).target,
+            PathBuf::from("Documents")
+        );
+    }
+}
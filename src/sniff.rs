@@ -0,0 +1,246 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+// Content sniffing: recognize a handful of common formats from their first
+// bytes, so files with a missing or wrong extension can still be classified.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Kind {
+    pub name: &'static str,
+    // Extension used for classification when the real one doesn't fit.
+    pub ext: &'static str,
+    pub mime: &'static str,
+    // Every extension that is plausible for this content.
+    pub extensions: &'static [&'static str],
+}
+
+impl Kind {
+    pub fn accepts(&self, ext: &str) -> bool {
+        self.extensions.iter().any(|e| e.eq_ignore_ascii_case(ext))
+    }
+}
+
+const PDF: Kind = Kind {
+    name: "PDF",
+    ext: "pdf",
+    mime: "application/pdf",
+    extensions: &["pdf", "ai"],
+};
+const PNG: Kind = Kind {
+    name: "PNG",
+    ext: "png",
+    mime: "image/png",
+    extensions: &["png", "apng"],
+};
+const JPEG: Kind = Kind {
+    name: "JPEG",
+    ext: "jpg",
+    mime: "image/jpeg",
+    extensions: &["jpg", "jpeg", "jpe", "jfif"],
+};
+const ZIP: Kind = Kind {
+    name: "ZIP",
+    ext: "zip",
+    mime: "application/zip",
+    // Lots of formats are zip files underneath.
+    extensions: &[
+        "zip", "jar", "war", "apk", "aab", "ipa", "whl", "epub", "xpi", "vsix", "nupkg", "odt",
+        "ods", "odp", "docx", "xlsx", "pptx", "docm", "xlsm", "pptm", "kmz", "3mf",
+    ],
+};
+const DOCX: Kind = Kind {
+    name: "Word document",
+    ext: "docx",
+    mime: "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    extensions: &["docx", "docm", "dotx", "dotm"],
+};
+const XLSX: Kind = Kind {
+    name: "Excel workbook",
+    ext: "xlsx",
+    mime: "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    extensions: &["xlsx", "xlsm", "xltx", "xltm"],
+};
+const PPTX: Kind = Kind {
+    name: "PowerPoint presentation",
+    ext: "pptx",
+    mime: "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    extensions: &["pptx", "pptm", "potx", "ppsx"],
+};
+const GZIP: Kind = Kind {
+    name: "gzip",
+    ext: "gz",
+    mime: "application/gzip",
+    extensions: &["gz", "tgz", "gzip"],
+};
+const ELF: Kind = Kind {
+    name: "ELF executable",
+    ext: "",
+    mime: "application/x-executable",
+    extensions: &["", "so", "o", "ko", "elf", "bin", "run", "appimage"],
+};
+const PE: Kind = Kind {
+    name: "Windows executable",
+    ext: "exe",
+    mime: "application/vnd.microsoft.portable-executable",
+    extensions: &["exe", "dll", "sys", "scr", "cpl", "ocx", "efi", "mui"],
+};
+const MP4: Kind = Kind {
+    name: "MP4/QuickTime video",
+    ext: "mp4",
+    mime: "video/mp4",
+    extensions: &[
+        "mp4", "m4v", "m4a", "m4b", "mov", "qt", "3gp", "3g2", "heic", "heif", "avif",
+    ],
+};
+
+// Enough to see past the first few zip entries of an Office document.
+const SNIFF_LEN: usize = 8192;
+
+// None when the file can't be read or isn't one of the known formats.
+pub fn sniff(path: &Path) -> Option<Kind> {
+    let mut buf = Vec::with_capacity(SNIFF_LEN);
+    File::open(path)
+        .ok()?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut buf)
+        .ok()?;
+    detect(&buf)
+}
+
+pub fn detect(buf: &[u8]) -> Option<Kind> {
+    if buf.starts_with(b"%PDF-") {
+        return Some(PDF);
+    }
+    if buf.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some(PNG);
+    }
+    if buf.starts_with(b"\xff\xd8\xff") {
+        return Some(JPEG);
+    }
+    if buf.starts_with(b"PK\x03\x04") || buf.starts_with(b"PK\x05\x06") {
+        return Some(office_kind(buf).unwrap_or(ZIP));
+    }
+    if buf.starts_with(b"\x1f\x8b") {
+        return Some(GZIP);
+    }
+    if buf.starts_with(b"\x7fELF") {
+        return Some(ELF);
+    }
+    if buf.starts_with(b"MZ") && is_pe(buf) {
+        return Some(PE);
+    }
+    if buf.len() >= 12 && &buf[4..8] == b"ftyp" {
+        return Some(MP4);
+    }
+    None
+}
+
+// OOXML files are zips whose entry names start with word/, xl/ or ppt/. Local
+// file headers store names uncompressed, so a substring search is enough.
+fn office_kind(buf: &[u8]) -> Option<Kind> {
+    let has = |needle: &[u8]| buf.windows(needle.len()).any(|w| w == needle);
+    if has(b"word/") {
+        Some(DOCX)
+    } else if has(b"xl/") {
+        Some(XLSX)
+    } else if has(b"ppt/") {
+        Some(PPTX)
+    } else {
+        None
+    }
+}
+
+// "MZ" alone is too weak; check that e_lfanew points at a "PE\0\0" header when
+// it's within what we read. Larger offsets are rare enough to accept as-is.
+fn is_pe(buf: &[u8]) -> bool {
+    let Some(offset) = buf.get(0x3c..0x40) else {
+        return false;
+    };
+    let offset = u32::from_le_bytes([offset[0], offset[1], offset[2], offset[3]]) as usize;
+    match offset.checked_add(4).and_then(|end| buf.get(offset..end)) {
+        Some(sig) => sig == b"PE\0\0",
+        None => offset >= buf.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The start of a zip whose first entry is `name`, as a local file header.
+    fn zip_with(name: &str) -> Vec<u8> {
+        let mut buf = b"PK\x03\x04".to_vec();
+        buf.extend([0u8; 22]);
+        buf.extend((name.len() as u16).to_le_bytes());
+        buf.extend([0u8; 2]);
+        buf.extend(name.as_bytes());
+        buf
+    }
+
+    // A DOS stub whose e_lfanew points at `offset`, `len` bytes long in all.
+    fn mz(offset: u32, len: usize) -> Vec<u8> {
+        let mut buf = vec![0u8; len];
+        buf[..2].copy_from_slice(b"MZ");
+        buf[0x3c..0x40].copy_from_slice(&offset.to_le_bytes());
+        buf
+    }
+
+    #[test]
+    fn detect_recognizes_formats_by_their_first_bytes() {
+        let name = |buf: &[u8]| detect(buf).map(|k| k.name);
+        assert_eq!(name(b"%PDF-1.7\n"), Some("PDF"));
+        assert_eq!(name(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), Some("PNG"));
+        assert_eq!(name(b"\xff\xd8\xff\xe0\0\x10JFIF"), Some("JPEG"));
+        assert_eq!(name(b"\x1f\x8b\x08\0"), Some("gzip"));
+        assert_eq!(name(b"\x7fELF\x02\x01\x01"), Some("ELF executable"));
+        assert_eq!(
+            name(b"\0\0\0\x18ftypisom\0\0\x02\0"),
+            Some("MP4/QuickTime video")
+        );
+        assert_eq!(name(b"PK\x05\x06"), Some("ZIP"));
+        assert_eq!(name(b"hello, world\n"), None);
+        assert_eq!(name(b""), None);
+    }
+
+    #[test]
+    fn headers_cut_short_are_not_recognized() {
+        assert_eq!(detect(b"%PDF"), None);
+        assert_eq!(detect(b"\x89PNG\r\n"), None);
+        assert_eq!(detect(b"\xff\xd8"), None);
+        assert_eq!(detect(b"PK\x03"), None);
+        assert_eq!(detect(b"\x7fEL"), None);
+        // `ftyp` is there, but not the brand after it.
+        assert_eq!(detect(b"\0\0\0\x18ftyp"), None);
+    }
+
+    #[test]
+    fn office_documents_are_told_apart_by_their_entries() {
+        assert_eq!(detect(&zip_with("word/document.xml")), Some(DOCX));
+        assert_eq!(detect(&zip_with("xl/workbook.xml")), Some(XLSX));
+        assert_eq!(detect(&zip_with("ppt/presentation.xml")), Some(PPTX));
+        assert_eq!(detect(&zip_with("src/main.rs")), Some(ZIP));
+
+        assert_eq!(office_kind(b"[Content_Types].xml word/"), Some(DOCX));
+        assert_eq!(office_kind(b"wor"), None);
+        assert!(ZIP.accepts("DOCX") && DOCX.accepts("docm") && !DOCX.accepts("xlsx"));
+    }
+
+    #[test]
+    fn is_pe_checks_the_header_e_lfanew_points_at() {
+        let mut exe = mz(0x80, 0x100);
+        exe[0x80..0x84].copy_from_slice(b"PE\0\0");
+        assert!(is_pe(&exe));
+        assert_eq!(detect(&exe), Some(PE));
+
+        // Something else where the PE header should be.
+        assert!(!is_pe(&mz(0x80, 0x100)));
+        assert_eq!(detect(&mz(0x80, 0x100)), None);
+        // Too short to even hold e_lfanew.
+        assert!(!is_pe(b"MZ\x90\0"));
+        assert!(!is_pe(&mz(0x80, 0x40)[..0x3e]));
+        // A header past what was read is taken on trust; one cut off is not.
+        assert!(is_pe(&mz(0x4000, 0x40)));
+        assert!(!is_pe(&mz(0x3e, 0x40)));
+    }
+}
@@ -0,0 +1,576 @@
+use crate::{ProjectItem, ScanReport, fmt_bytes, git};
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use ratatui::DefaultTerminal;
+use ratatui::Frame;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Cell, Clear, Paragraph, Row, Table, TableState, Wrap};
+use std::path::Path;
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+// `sweeper scan --interactive`: pick stale projects by hand, then archive or
+// trash just those. The UI only decides; the caller does the work, through the
+// same plan/apply/journal path as `archive` and `delete`. Checkouts with
+// unsaved git work are listed but can't be picked unless `--force-dirty`.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Archive,
+    Trash,
+}
+
+#[derive(Debug, Clone)]
+pub struct Selection {
+    pub action: Action,
+    pub items: Vec<ProjectItem>,
+}
+
+// How many lines of the folder tree and how many files the preview shows.
+const PREVIEW_TREE_DEPTH: usize = 2;
+const PREVIEW_TREE_LINES: usize = 200;
+const PREVIEW_LARGEST: usize = 10;
+
+#[derive(Debug, Clone)]
+enum GitState {
+    NotARepo,
+    Clean,
+    Unsaved(Vec<String>),
+}
+
+impl GitState {
+    fn of(dir: &Path) -> Self {
+        if !git::is_repo(dir) {
+            return GitState::NotARepo;
+        }
+        match git::unsaved_work(dir) {
+            reasons if reasons.is_empty() => GitState::Clean,
+            reasons => GitState::Unsaved(reasons),
+        }
+    }
+
+    fn label(&self) -> String {
+        match self {
+            GitState::NotARepo => "-".to_string(),
+            GitState::Clean => "clean".to_string(),
+            GitState::Unsaved(reasons) => reasons[0].clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sort {
+    Age,
+    Size,
+    Name,
+}
+
+impl Sort {
+    fn next(self) -> Self {
+        match self {
+            Sort::Age => Sort::Size,
+            Sort::Size => Sort::Name,
+            Sort::Name => Sort::Age,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Sort::Age => "oldest first",
+            Sort::Size => "largest first",
+            Sort::Name => "name",
+        }
+    }
+}
+
+struct Entry {
+    item: ProjectItem,
+    name: String,
+    git: GitState,
+    selected: bool,
+}
+
+impl Entry {
+    fn disk_bytes(&self) -> u64 {
+        self.item.size.map(|s| s.disk_bytes).unwrap_or(0)
+    }
+
+    fn has_unsaved_work(&self) -> bool {
+        matches!(self.git, GitState::Unsaved(_))
+    }
+}
+
+enum Mode {
+    List,
+    Filter,
+    Preview {
+        title: String,
+        lines: Vec<String>,
+        scroll: u16,
+    },
+    Confirm(Action),
+}
+
+struct App {
+    root: String,
+    // None when the roots were scanned with different thresholds.
+    older_than_days: Option<u64>,
+    entries: Vec<Entry>,
+    // Indexes into `entries` that pass the filter, in display order.
+    visible: Vec<usize>,
+    sort: Sort,
+    filter: String,
+    table: TableState,
+    mode: Mode,
+    can_archive: bool,
+    // Whether projects with unsaved git work may be picked.
+    allow_dirty: bool,
+    message: Option<String>,
+}
+
+// Show the stale projects of `report` and let the user pick some and an action.
+// Returns None when they quit without choosing anything to do.
+pub fn select(
+    report: &ScanReport,
+    can_archive: bool,
+    allow_dirty: bool,
+) -> Result<Option<Selection>> {
+    let entries = report
+        .stale
+        .iter()
+        .map(|item| Entry {
+            name: item
+                .path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| item.path.display().to_string()),
+            git: GitState::of(&item.path),
+            item: item.clone(),
+            selected: false,
+        })
+        .collect();
+
+    let mut app = App {
+        root: report
+            .roots
+            .iter()
+            .map(|r| r.path.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+        older_than_days: report.older_than_days(),
+        entries,
+        visible: Vec::new(),
+        sort: Sort::Age,
+        filter: String::new(),
+        table: TableState::default(),
+        mode: Mode::List,
+        can_archive,
+        allow_dirty,
+        message: None,
+    };
+    app.refresh();
+
+    ratatui::run(|terminal| app.run(terminal))
+}
+
+impl App {
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<Option<Selection>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if let Some(done) = self.handle(key) {
+                return Ok(done);
+            }
+        }
+    }
+
+    // Some(result) ends the UI.
+    fn handle(&mut self, key: KeyEvent) -> Option<Option<Selection>> {
+        self.message = None;
+        match &mut self.mode {
+            Mode::Filter => match key.code {
+                KeyCode::Enter => self.mode = Mode::List,
+                KeyCode::Esc => {
+                    self.filter.clear();
+                    self.mode = Mode::List;
+                    self.refresh();
+                }
+                KeyCode::Backspace => {
+                    self.filter.pop();
+                    self.refresh();
+                }
+                KeyCode::Char(c) => {
+                    self.filter.push(c);
+                    self.refresh();
+                }
+                _ => {}
+            },
+            Mode::Preview { scroll, .. } => match key.code {
+                KeyCode::Up | KeyCode::Char('k') => *scroll = scroll.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('j') => *scroll = scroll.saturating_add(1),
+                KeyCode::PageUp => *scroll = scroll.saturating_sub(20),
+                KeyCode::PageDown => *scroll = scroll.saturating_add(20),
+                _ => self.mode = Mode::List,
+            },
+            Mode::Confirm(action) => {
+                let action = *action;
+                match key.code {
+                    KeyCode::Char('y') | KeyCode::Char('Y') => {
+                        return Some(Some(Selection {
+                            action,
+                            items: self.selected().map(|e| e.item.clone()).collect(),
+                        }));
+                    }
+                    _ => self.mode = Mode::List,
+                }
+            }
+            Mode::List => match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Some(None),
+                KeyCode::Up | KeyCode::Char('k') => self.table.select_previous(),
+                KeyCode::Down | KeyCode::Char('j') => self.table.select_next(),
+                KeyCode::PageUp => self.table.scroll_up_by(20),
+                KeyCode::PageDown => self.table.scroll_down_by(20),
+                KeyCode::Home | KeyCode::Char('g') => self.table.select_first(),
+                KeyCode::End | KeyCode::Char('G') => self.table.select_last(),
+                KeyCode::Char(' ') => {
+                    if let Some(idx) = self.current() {
+                        if self.locked(&self.entries[idx]) {
+                            self.message = Some(
+                                "Held back: unsaved git work (rerun with --force-dirty to include it)"
+                                    .to_string(),
+                            );
+                        } else {
+                            self.entries[idx].selected ^= true;
+                            self.table.select_next();
+                        }
+                    }
+                }
+                KeyCode::Char('a') => {
+                    let pickable: Vec<usize> = self
+                        .visible
+                        .iter()
+                        .copied()
+                        .filter(|&i| !self.locked(&self.entries[i]))
+                        .collect();
+                    let all = pickable.iter().all(|&i| self.entries[i].selected);
+                    for i in pickable {
+                        self.entries[i].selected = !all;
+                    }
+                }
+                KeyCode::Char('s') => {
+                    self.sort = self.sort.next();
+                    self.refresh();
+                }
+                KeyCode::Char('/') => self.mode = Mode::Filter,
+                KeyCode::Enter | KeyCode::Char('p') => {
+                    if let Some(idx) = self.current() {
+                        let path = &self.entries[idx].item.path;
+                        self.mode = Mode::Preview {
+                            title: path.display().to_string(),
+                            lines: preview(path),
+                            scroll: 0,
+                        };
+                    }
+                }
+                KeyCode::Char('A') => self.confirm(Action::Archive),
+                KeyCode::Char('D') => self.confirm(Action::Trash),
+                _ => {}
+            },
+        }
+        None
+    }
+
+    fn confirm(&mut self, action: Action) {
+        if self.selected().next().is_none() {
+            self.message = Some("Nothing selected (space toggles a project)".to_string());
+        } else if action == Action::Archive && !self.can_archive {
+            self.message =
+                Some("No archive destination: pass --dest or set `archive.dest`".to_string());
+        } else {
+            self.mode = Mode::Confirm(action);
+        }
+    }
+
+    fn locked(&self, entry: &Entry) -> bool {
+        !self.allow_dirty && entry.has_unsaved_work()
+    }
+
+    fn current(&self) -> Option<usize> {
+        self.table
+            .selected()
+            .and_then(|i| self.visible.get(i).copied())
+    }
+
+    fn selected(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter().filter(|e| e.selected)
+    }
+
+    // Re-apply sort and filter, keeping the cursor on the same project.
+    fn refresh(&mut self) {
+        let current = self.current().map(|i| self.entries[i].item.path.clone());
+
+        match self.sort {
+            Sort::Age => self.entries.sort_by_key(|e| e.item.last_modified),
+            Sort::Size => self
+                .entries
+                .sort_by_key(|e| std::cmp::Reverse(e.disk_bytes())),
+            Sort::Name => self.entries.sort_by_key(|e| e.name.to_lowercase()),
+        }
+
+        let filter = self.filter.to_lowercase();
+        self.visible = (0..self.entries.len())
+            .filter(|&i| {
+                filter.is_empty()
+                    || self.entries[i]
+                        .item
+                        .path
+                        .to_string_lossy()
+                        .to_lowercase()
+                        .contains(&filter)
+            })
+            .collect();
+
+        let pos = current
+            .and_then(|c| {
+                self.visible
+                    .iter()
+                    .position(|&i| self.entries[i].item.path == c)
+            })
+            .or((!self.visible.is_empty()).then_some(0));
+        self.table.select(pos);
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        if let Mode::Preview {
+            title,
+            lines,
+            scroll,
+        } = &self.mode
+        {
+            let text: Vec<Line> = lines.iter().map(|l| Line::from(l.as_str())).collect();
+            frame.render_widget(
+                Paragraph::new(text)
+                    .scroll((*scroll, 0))
+                    .block(Block::bordered().title(format!(" {} ", title))),
+                frame.area(),
+            );
+            return;
+        }
+
+        let [header, body, status, help] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(3),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let (count, bytes) = self
+            .selected()
+            .fold((0, 0), |(n, b), e| (n + 1, b + e.disk_bytes()));
+        frame.render_widget(
+            Line::from(format!(
+                " {} stale project(s) in {} ({}) | selected: {} ({}) | sort: {}",
+                self.visible.len(),
+                self.root,
+                match self.older_than_days {
+                    Some(days) => format!("no activity for {}+ days", days),
+                    None => "each root's threshold".to_string(),
+                },
+                count,
+                fmt_bytes(bytes),
+                self.sort.label()
+            ))
+            .bold(),
+            header,
+        );
+
+        let now = SystemTime::now();
+        let rows: Vec<Row> = self
+            .visible
+            .iter()
+            .map(|&i| {
+                let e = &self.entries[i];
+                let days = now
+                    .duration_since(e.item.last_modified)
+                    .map(|d| d.as_secs() / 86_400)
+                    .unwrap_or(0);
+                let last: DateTime<Local> = e.item.last_modified.into();
+                let size = e
+                    .item
+                    .size
+                    .map(|s| fmt_bytes(s.disk_bytes))
+                    .unwrap_or_else(|| "?".to_string());
+                let git = match &e.git {
+                    GitState::Unsaved(_) => Cell::from(e.git.label()).fg(Color::Yellow),
+                    _ => Cell::from(e.git.label()),
+                };
+                let mark = match (e.selected, self.locked(e)) {
+                    (_, true) => "[-]",
+                    (true, false) => "[x]",
+                    (false, false) => "[ ]",
+                };
+                Row::new(vec![
+                    Cell::from(mark),
+                    Cell::from(e.name.clone()),
+                    Cell::from(e.item.kind.label()),
+                    Cell::from(format!("{}d", days)),
+                    Cell::from(last.format("%Y-%m-%d").to_string()),
+                    Cell::from(size),
+                    git,
+                ])
+            })
+            .collect();
+
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(3),
+                Constraint::Fill(2),
+                Constraint::Length(12),
+                Constraint::Length(6),
+                Constraint::Length(10),
+                Constraint::Length(10),
+                Constraint::Fill(3),
+            ],
+        )
+        .header(
+            Row::new(["", "Project", "Kind", "Age", "Last", "Size", "Git"])
+                .style(Style::new().add_modifier(Modifier::UNDERLINED)),
+        )
+        .column_spacing(2)
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+        .block(Block::bordered());
+        frame.render_stateful_widget(table, body, &mut self.table);
+
+        let status_line = match (&self.mode, &self.message) {
+            (Mode::Filter, _) => Line::from(vec![
+                Span::raw(" filter: "),
+                Span::raw(self.filter.as_str()).bold(),
+                Span::raw("_"),
+            ]),
+            (_, Some(msg)) => Line::from(format!(" {}", msg)).fg(Color::Yellow),
+            _ if !self.filter.is_empty() => Line::from(format!(" filter: {}", self.filter)),
+            _ => Line::default(),
+        };
+        frame.render_widget(status_line, status);
+        frame.render_widget(
+            Line::from(
+                " space toggle  a all  s sort  / filter  enter preview  A archive  D trash  q quit",
+            )
+            .dim(),
+            help,
+        );
+
+        if let Mode::Confirm(action) = self.mode {
+            self.draw_confirm(frame, action);
+        }
+    }
+
+    fn draw_confirm(&self, frame: &mut Frame, action: Action) {
+        let (count, bytes) = self
+            .selected()
+            .fold((0, 0), |(n, b), e| (n + 1, b + e.disk_bytes()));
+        let unsaved = self.selected().filter(|e| e.has_unsaved_work()).count();
+        let verb = match action {
+            Action::Archive => "Archive",
+            Action::Trash => "Move to system bin",
+        };
+
+        let mut lines = vec![Line::from(format!(
+            "{} {} project(s), {}?",
+            verb,
+            count,
+            fmt_bytes(bytes)
+        ))];
+        if unsaved > 0 {
+            lines.push(
+                Line::from(format!(
+                    "Includes {} with unsaved git work (see the Git column).",
+                    unsaved
+                ))
+                .fg(Color::Yellow),
+            );
+        }
+        lines.push(Line::default());
+        lines.push(Line::from("y: go ahead   any other key: back").dim());
+
+        let area = centered(frame.area(), 60, lines.len() as u16 + 2);
+        frame.render_widget(Clear, area);
+        frame.render_widget(
+            Paragraph::new(lines)
+                .wrap(Wrap { trim: true })
+                .block(Block::bordered().title(" Confirm ")),
+            area,
+        );
+    }
+}
+
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    area.centered(
+        Constraint::Length(width.min(area.width)),
+        Constraint::Length(height.min(area.height)),
+    )
+}
+
+// The largest files in `dir`, then its top levels as a tree.
+fn preview(dir: &Path) -> Vec<String> {
+    let mut files: Vec<(u64, String)> = WalkDir::new(dir)
+        .follow_links(false)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| {
+            let len = e.metadata().ok()?.len();
+            let rel = e.path().strip_prefix(dir).ok()?.display().to_string();
+            Some((len, rel))
+        })
+        .collect();
+    files.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+
+    let mut lines = vec!["Largest files:".to_string()];
+    if files.is_empty() {
+        lines.push("  (none)".to_string());
+    }
+    for (len, rel) in files.iter().take(PREVIEW_LARGEST) {
+        lines.push(format!("  {:>10}  {}", fmt_bytes(*len), rel));
+    }
+
+    lines.push(String::new());
+    lines.push("Tree:".to_string());
+    let tree = WalkDir::new(dir)
+        .follow_links(false)
+        .min_depth(1)
+        .max_depth(PREVIEW_TREE_DEPTH)
+        .sort_by(|a, b| {
+            b.file_type()
+                .is_dir()
+                .cmp(&a.file_type().is_dir())
+                .then(a.file_name().cmp(b.file_name()))
+        })
+        .into_iter()
+        .filter_map(|e| e.ok());
+    for (i, e) in tree.enumerate() {
+        if i == PREVIEW_TREE_LINES {
+            lines.push("  …".to_string());
+            break;
+        }
+        let indent = "  ".repeat(e.depth());
+        let slash = if e.file_type().is_dir() { "/" } else { "" };
+        lines.push(format!(
+            "{}{}{}",
+            indent,
+            e.file_name().to_string_lossy(),
+            slash
+        ));
+    }
+    lines
+}
@@ -0,0 +1,233 @@
+use crate::filter::{self, Filter};
+use crate::output::{self, OutputFormat};
+use crate::rules::RuleSet;
+use crate::{OrganizeOptions, apply_organize, journal, plan_organize_file};
+use anyhow::{Context, Result};
+use chrono::Local;
+use notify::{Event, RecursiveMode, Watcher};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime};
+
+// Watch mode: organize files as they arrive in a folder. A new file is only
+// touched once it has stopped changing for `settle` and doesn't look like a
+// download still in progress.
+
+// Suffixes browsers and downloaders use while a file is still being written.
+const PARTIAL_SUFFIXES: &[&str] = &[".part", ".crdownload", ".tmp", ".download", ".partial"];
+
+// How often pending files are re-checked.
+const TICK: Duration = Duration::from_millis(500);
+
+pub struct WatchOptions {
+    pub settle: Duration,
+    pub organize: OrganizeOptions,
+    pub filter: Filter,
+    pub dry_run: bool,
+    pub format: OutputFormat,
+}
+
+enum Message {
+    Fs(notify::Result<Event>),
+    Stop,
+}
+
+// Last seen state of a file that hasn't settled yet.
+struct Pending {
+    size: u64,
+    modified: Option<SystemTime>,
+    since: Instant,
+}
+
+pub fn is_partial(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    PARTIAL_SUFFIXES.iter().any(|s| name.ends_with(s))
+}
+
+// Runs until SIGINT or SIGTERM.
+pub fn watch(root: &Path, rules: &RuleSet, opts: &WatchOptions) -> Result<()> {
+    // A document per file wouldn't add up to one valid JSON or CSV stream.
+    if matches!(opts.format, OutputFormat::Json | OutputFormat::Csv) {
+        anyhow::bail!("watch writes one record per line; use --format ndjson");
+    }
+    let root = std::path::absolute(root)
+        .with_context(|| format!("Failed to resolve path: {}", root.display()))?;
+    if !root.is_dir() {
+        anyhow::bail!("Not a directory: {}", root.display());
+    }
+
+    let (tx, rx) = mpsc::channel();
+
+    let stop = tx.clone();
+    ctrlc::set_handler(move || {
+        let _ = stop.send(Message::Stop);
+    })
+    .context("Failed to install signal handler")?;
+
+    let mut watcher = notify::recommended_watcher(move |res| {
+        let _ = tx.send(Message::Fs(res));
+    })
+    .context("Failed to start file watcher")?;
+    watcher
+        .watch(&root, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Failed to watch: {}", root.display()))?;
+
+    let text = opts.format == OutputFormat::Text;
+    if text {
+        log(&format!(
+            "Watching {} (files settle after {}s). Press Ctrl+C to stop.",
+            root.display(),
+            opts.settle.as_secs()
+        ));
+    }
+
+    let mut pending: HashMap<PathBuf, Pending> = HashMap::new();
+
+    loop {
+        match rx.recv_timeout(TICK) {
+            Ok(Message::Stop) | Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            Ok(Message::Fs(Ok(event))) => {
+                for path in event.paths {
+                    if path.parent() == Some(root.as_path()) {
+                        touch(&mut pending, path);
+                    }
+                }
+            }
+            Ok(Message::Fs(Err(e))) => warn(&format!("Watcher error: {}", e)),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+        }
+
+        for path in settled(&mut pending, opts.settle) {
+            if let Err(e) = organize_one(&root, &path, rules, opts) {
+                warn(&format!("Failed to organize '{}': {:#}", path.display(), e));
+            }
+        }
+    }
+
+    if text {
+        if !pending.is_empty() {
+            log(&format!(
+                "{} file(s) had not settled yet and were left in place.",
+                pending.len()
+            ));
+        }
+        log(&format!("Stopped watching {}.", root.display()));
+    }
+    Ok(())
+}
+
+// Something happened to `path`: (re)start its settle timer.
+fn touch(pending: &mut HashMap<PathBuf, Pending>, path: PathBuf) {
+    match fs::metadata(&path) {
+        Ok(meta) if meta.is_file() && !is_partial(&path) => {
+            pending.insert(
+                path,
+                Pending {
+                    size: meta.len(),
+                    modified: meta.modified().ok(),
+                    since: Instant::now(),
+                },
+            );
+        }
+        // Gone, a directory, or still downloading. A finished download shows up
+        // again under its final name.
+        _ => {
+            pending.remove(&path);
+        }
+    }
+}
+
+// Take the files whose size and mtime haven't changed for `settle`.
+fn settled(pending: &mut HashMap<PathBuf, Pending>, settle: Duration) -> Vec<PathBuf> {
+    let mut ready = Vec::new();
+    pending.retain(|path, p| {
+        let Ok(meta) = fs::metadata(path) else {
+            return false;
+        };
+        let modified = meta.modified().ok();
+        if meta.len() != p.size || modified != p.modified {
+            p.size = meta.len();
+            p.modified = modified;
+            p.since = Instant::now();
+            return true;
+        }
+        if p.since.elapsed() >= settle {
+            ready.push(path.clone());
+            return false;
+        }
+        true
+    });
+    ready.sort();
+    ready
+}
+
+fn organize_one(root: &Path, path: &Path, rules: &RuleSet, opts: &WatchOptions) -> Result<()> {
+    // Read every time, so edits to `.sweeperignore` apply without a restart.
+    // Until a broken one is fixed, files are left where they are.
+    let rule = match filter::load_ignore(root) {
+        Ok(ignore) => {
+            let ignores: Vec<_> = ignore.into_iter().collect();
+            opts.filter.check(root, path, false, &ignores)
+        }
+        Err(e) => Some(format!("{:#}", e)),
+    };
+    if let Some(rule) = rule {
+        if opts.format == OutputFormat::Text {
+            log(&format!("Left '{}' alone ({})", path.display(), rule));
+        }
+        return Ok(());
+    }
+    let Some(mv) = plan_organize_file(root, path, rules, &opts.organize, &mut HashSet::new())?
+    else {
+        return Ok(());
+    };
+    let moves = [mv];
+
+    let mut run_id = None;
+    if !opts.dry_run && !moves[0].identical {
+        let run = journal::record("watch", |run| apply_organize(&moves, run))?;
+        run_id = Some(run.id);
+    }
+
+    if opts.format == OutputFormat::Text {
+        let mv = &moves[0];
+        if mv.identical {
+            log(&format!(
+                "Left '{}' in place (identical to '{}')",
+                mv.from.display(),
+                mv.to.display()
+            ));
+            return Ok(());
+        }
+        let verb = if opts.dry_run { "Would move" } else { "Moved" };
+        let undo = match &run_id {
+            Some(id) => format!(", undo with: sweeper undo {}", id),
+            None => String::new(),
+        };
+        log(&format!(
+            "{} '{}' -> '{}' ({}{})",
+            verb,
+            mv.from.display(),
+            mv.to.display(),
+            mv.reason,
+            undo
+        ));
+    } else {
+        output::organize_document(root, &moves, run_id.as_deref()).write(opts.format)?;
+    }
+    Ok(())
+}
+
+fn log(message: &str) {
+    println!("[{}] {}", Local::now().format("%Y-%m-%d %H:%M:%S"), message);
+}
+
+// Problems go to stderr so they never end up inside machine-readable output.
+fn warn(message: &str) {
+    eprintln!("[{}] {}", Local::now().format("%Y-%m-%d %H:%M:%S"), message);
+}